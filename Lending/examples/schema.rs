@@ -0,0 +1,49 @@
+use std::env::current_dir;
+use std::fs::create_dir_all;
+
+use cosmwasm_schema::{export_schema, export_schema_with_title, remove_schemas, schema_for};
+use cw20::{AllowanceResponse, BalanceResponse, TokenInfoResponse};
+
+use lending_dapp::msg::{
+    AuctionResponse, ConfigResponse, Cw20HookMsg, ExecuteMsg, InstantiateMsg, LoanResponse,
+    LockResponse, MigrateMsg, PendingChangeResponse, PendingRewardsResponse, PoolInfoResponse,
+    QueryMsg, RatesResponse, SimulationResponse, UnbondingResponse, UserInfoResponse,
+};
+
+fn main() {
+    let mut out_dir = current_dir().unwrap();
+    out_dir.push("schema");
+    create_dir_all(&out_dir).unwrap();
+    remove_schemas(&out_dir).unwrap();
+
+    export_schema(&schema_for!(InstantiateMsg), &out_dir);
+    export_schema(&schema_for!(ExecuteMsg), &out_dir);
+    export_schema(&schema_for!(Cw20HookMsg), &out_dir);
+    export_schema(&schema_for!(QueryMsg), &out_dir);
+    export_schema(&schema_for!(MigrateMsg), &out_dir);
+
+    export_schema(&schema_for!(ConfigResponse), &out_dir);
+    export_schema(&schema_for!(UserInfoResponse), &out_dir);
+    export_schema(&schema_for!(PoolInfoResponse), &out_dir);
+    export_schema_with_title(&schema_for!(Vec<LockResponse>), &out_dir, "GetUserLocksResponse");
+    export_schema_with_title(&schema_for!(Vec<UnbondingResponse>), &out_dir, "GetUnbondingResponse");
+    export_schema_with_title(&schema_for!(LoanResponse), &out_dir, "GetLoanResponse");
+    export_schema_with_title(&schema_for!(Vec<LoanResponse>), &out_dir, "GetUserLoansResponse");
+    export_schema_with_title(&schema_for!(AuctionResponse), &out_dir, "GetAuctionResponse");
+    export_schema_with_title(&schema_for!(Vec<AuctionResponse>), &out_dir, "GetAuctionsResponse");
+    export_schema_with_title(&schema_for!(RatesResponse), &out_dir, "GetRatesResponse");
+    export_schema_with_title(
+        &schema_for!(PendingRewardsResponse),
+        &out_dir,
+        "GetPendingRewardsResponse",
+    );
+    export_schema_with_title(
+        &schema_for!(Vec<PendingChangeResponse>),
+        &out_dir,
+        "GetPendingChangesResponse",
+    );
+    export_schema(&schema_for!(SimulationResponse), &out_dir);
+    export_schema(&schema_for!(BalanceResponse), &out_dir);
+    export_schema(&schema_for!(TokenInfoResponse), &out_dir);
+    export_schema(&schema_for!(AllowanceResponse), &out_dir);
+}
@@ -1,4 +1,5 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{DivideByZeroError, OverflowError, StdError};
+use cw_utils::ParseReplyError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -6,21 +7,93 @@ pub enum ContractError {
     #[error("{0}")]
     Std(#[from] StdError),
 
+    #[error("{0}")]
+    ParseReply(#[from] ParseReplyError),
+
+    #[error("{0}")]
+    Overflow(#[from] OverflowError),
+
+    #[error("{0}")]
+    DivideByZero(#[from] DivideByZeroError),
+
     #[error("Unauthorized")]
     Unauthorized {},
 
+    #[error("Invalid config value for {field}")]
+    InvalidConfig { field: String },
+
     #[error("Invalid CW20 hook")]
     InvalidCw20Hook {},
 
-    #[error("No funds sent")]
-    NoFunds {},
+    #[error("No position for this address")]
+    NoPosition {},
 
     #[error("Insufficient funds")]
     InsufficientFunds {},
 
+    #[error("Insufficient OM liquidity in the pool")]
+    InsufficientLiquidity {},
+
     #[error("Exceeds collateral ratio")]
     ExceedsCollateralRatio {},
 
     #[error("Excess repayment")]
     ExcessRepayment {},
+
+    #[error("Loan term must be greater than zero")]
+    InvalidTerm {},
+
+    #[error("Loan is not past due")]
+    LoanNotPastDue {},
+
+    #[error("Amount must be greater than zero")]
+    InvalidZeroAmount {},
+
+    #[error("Existing debt uses a different rate mode")]
+    RateModeMismatch {},
+
+    #[error("Utilization is below the rebalance threshold")]
+    RebalanceConditionNotMet {},
+
+    #[error("Insufficient debt delegation")]
+    InsufficientDelegation {},
+
+    #[error("Lock duration must be 30, 90 or 180 days")]
+    InvalidLockDuration {},
+
+    #[error("Funds are locked")]
+    FundsLocked {},
+
+    #[error("Nothing to claim")]
+    NothingToClaim {},
+
+    #[error("No payout to claim")]
+    NoPayout {},
+
+    #[error("Payout is held while the token is paused or the recipient is blocked")]
+    PayoutHeld {},
+
+    #[error("No rewards to claim")]
+    NoRewards {},
+
+    #[error("Reward reserve is too low to pay this claim")]
+    InsufficientRewardReserve {},
+
+    #[error("Position is healthy")]
+    PositionHealthy {},
+
+    #[error("No open debt")]
+    NoDebt {},
+
+    #[error("Timelock has not expired, executable at {eta}")]
+    TimelockNotExpired { eta: u64 },
+
+    #[error("Permit spender must be the lending contract")]
+    InvalidPermit {},
+
+    #[error("Receipt token has not been instantiated")]
+    ReceiptTokenNotSet {},
+
+    #[error("Unknown reply id: {id}")]
+    UnknownReplyId { id: u64 },
 }
\ No newline at end of file
@@ -0,0 +1,100 @@
+use cosmwasm_std::{Addr, Event, Uint128};
+
+use crate::state::{PoolInfo, UserInfo};
+
+/// Event emitted for every change to a position, reported by the chain as
+/// `wasm-lending_<action>`. All position events share these attributes; some
+/// add extra ones after them, such as `loan_id`.
+pub fn position_event(
+    action: &str,
+    user: &Addr,
+    asset: &Addr,
+    amount: Uint128,
+    position: &UserInfo,
+    pool: &PoolInfo,
+    now: u64,
+) -> Event {
+    Event::new(format!("lending_{}", action))
+        .add_attribute("user", user)
+        .add_attribute("asset", asset)
+        .add_attribute("amount", amount.to_string())
+        .add_attribute("staked_amount", position.staked_amount.to_string())
+        .add_attribute("locked_amount", position.locked_amount.to_string())
+        .add_attribute("borrowed_amount", position.borrowed_amount.to_string())
+        .add_attribute("fixed_borrowed", position.fixed_borrowed.to_string())
+        .add_attribute("total_staked", pool.total_staked.to_string())
+        .add_attribute("total_borrowed", pool.total_borrowed.to_string())
+        .add_attribute("borrow_index", pool.borrow_index.to_string())
+        .add_attribute("block_time", now.to_string())
+}
+
+/// Event for changes that touch no position, such as governance actions.
+pub fn admin_event(action: &str, now: u64) -> Event {
+    Event::new(format!("lending_{}", action)).add_attribute("block_time", now.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::Decimal;
+
+    #[test]
+    fn position_event_schema() {
+        let user = UserInfo {
+            staked_amount: Uint128::new(1000),
+            borrowed_amount: Uint128::new(400),
+            ..UserInfo::default()
+        };
+        let pool = PoolInfo {
+            total_staked: Uint128::new(5000),
+            total_borrowed: Uint128::new(2000),
+            total_boost: Uint128::zero(),
+            variable_borrowed: Uint128::new(2000),
+            stable_borrowed: Uint128::zero(),
+            stable_weighted_rate: Uint128::zero(),
+            borrow_index: Decimal::one(),
+            last_accrual: 10,
+        };
+
+        let event = position_event(
+            "borrow",
+            &Addr::unchecked("alice"),
+            &Addr::unchecked("om"),
+            Uint128::new(400),
+            &user,
+            &pool,
+            10,
+        );
+
+        assert_eq!(event.ty, "lending_borrow");
+        let attributes: Vec<(&str, &str)> = event
+            .attributes
+            .iter()
+            .map(|attr| (attr.key.as_str(), attr.value.as_str()))
+            .collect();
+        assert_eq!(
+            attributes,
+            vec![
+                ("user", "alice"),
+                ("asset", "om"),
+                ("amount", "400"),
+                ("staked_amount", "1000"),
+                ("locked_amount", "0"),
+                ("borrowed_amount", "400"),
+                ("fixed_borrowed", "0"),
+                ("total_staked", "5000"),
+                ("total_borrowed", "2000"),
+                ("borrow_index", "1"),
+                ("block_time", "10"),
+            ]
+        );
+    }
+
+    #[test]
+    fn admin_event_schema() {
+        let event = admin_event("queue_config_change", 10);
+        assert_eq!(event.ty, "lending_queue_config_change");
+        assert_eq!(event.attributes.len(), 1);
+        assert_eq!(event.attributes[0].key, "block_time");
+    }
+}
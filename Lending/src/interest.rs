@@ -0,0 +1,218 @@
+use cosmwasm_std::{Decimal, Uint128};
+
+use crate::error::ContractError;
+use crate::math::{mul_decimal, mul_decimals, mul_ratio, ratio, sub_pool_total, Rounding};
+use crate::msg::RateMode;
+use crate::state::{Config, Loan, PoolInfo, UserInfo};
+
+pub const SECONDS_PER_YEAR: u64 = 31_536_000;
+
+/// Simple interest on `principal` at `rate` percent per year over `elapsed`
+/// seconds. Interest is owed to the pool, so it rounds up.
+pub fn accrued_interest(
+    principal: Uint128,
+    rate: Uint128,
+    elapsed: u64,
+) -> Result<Uint128, ContractError> {
+    let numerator = rate.full_mul(elapsed);
+    mul_ratio(
+        principal,
+        numerator,
+        100u128 * SECONDS_PER_YEAR as u128,
+        Rounding::Up,
+    )
+}
+
+/// Share of staked funds that is currently lent out, capped at 100%.
+pub fn utilization(pool: &PoolInfo) -> Decimal {
+    if pool.total_staked.is_zero() {
+        return Decimal::zero();
+    }
+    Decimal::from_ratio(
+        pool.total_borrowed.min(pool.total_staked),
+        pool.total_staked,
+    )
+}
+
+/// Current variable borrow rate in percent per year: the base `interest_rate`
+/// plus `rate_slope` scaled by utilization. New stable and fixed-term borrows
+/// lock in this rate.
+pub fn variable_rate(config: &Config, pool: &PoolInfo) -> Result<Uint128, ContractError> {
+    let slope = mul_decimal(config.rate_slope, utilization(pool), Rounding::Up)?;
+    Ok(config.interest_rate.checked_add(slope)?)
+}
+
+/// Debt-weighted average rate across all stable-rate borrowers.
+pub fn average_stable_rate(pool: &PoolInfo) -> Result<Decimal, ContractError> {
+    if pool.stable_borrowed.is_zero() {
+        return Ok(Decimal::zero());
+    }
+    ratio(pool.stable_weighted_rate, pool.stable_borrowed)
+}
+
+/// Open-ended plus fixed-term debt of a position.
+pub fn total_debt(user: &UserInfo) -> Result<Uint128, ContractError> {
+    Ok(user.borrowed_amount.checked_add(user.fixed_borrowed)?)
+}
+
+/// Most a position may owe against its collateral. Rounds down.
+pub fn borrow_limit(config: &Config, user: &UserInfo) -> Result<Uint128, ContractError> {
+    mul_ratio(
+        user.staked_amount,
+        config.collateral_ratio,
+        100u128,
+        Rounding::Down,
+    )
+}
+
+/// Grows the pool's debt totals and borrow index up to `now`. Stable debt
+/// accrues in aggregate through `stable_weighted_rate`, which matches the sum
+/// of each stable borrower's own accrual up to rounding. The totals round
+/// down, so they never exceed the debts positions accrue, which round up.
+pub fn accrue_pool(config: &Config, pool: &mut PoolInfo, now: u64) -> Result<(), ContractError> {
+    let elapsed = now.saturating_sub(pool.last_accrual);
+    if elapsed == 0 {
+        return Ok(());
+    }
+
+    let rate = variable_rate(config, pool)?;
+    let variable_interest = mul_ratio(
+        pool.variable_borrowed,
+        rate.full_mul(elapsed),
+        100u128 * SECONDS_PER_YEAR as u128,
+        Rounding::Down,
+    )?;
+    let stable_interest = mul_ratio(
+        pool.stable_weighted_rate,
+        elapsed,
+        100u128 * SECONDS_PER_YEAR as u128,
+        Rounding::Down,
+    )?;
+    pool.variable_borrowed = pool.variable_borrowed.checked_add(variable_interest)?;
+    pool.stable_borrowed = pool.stable_borrowed.checked_add(stable_interest)?;
+    pool.total_borrowed = pool
+        .total_borrowed
+        .checked_add(variable_interest)?
+        .checked_add(stable_interest)?;
+
+    // The index grows by `rate * elapsed / year`, rounded up at 18 decimals.
+    let growth = Decimal::new(mul_ratio(
+        Uint128::from(elapsed),
+        rate.full_mul(Decimal::one().atomics()),
+        100u128 * SECONDS_PER_YEAR as u128,
+        Rounding::Up,
+    )?);
+    pool.borrow_index = mul_decimals(pool.borrow_index, Decimal::one().checked_add(growth)?)?;
+    pool.last_accrual = now;
+    Ok(())
+}
+
+/// Brings `user.borrowed_amount` up to `now`. Variable debt follows the pool
+/// borrow index; stable debt accrues at the user's own rate. The pool totals
+/// already include this interest, so only the stable weight is updated here.
+/// `accrue_pool` must have been called first.
+pub fn accrue_user(
+    pool: &mut PoolInfo,
+    user: &mut UserInfo,
+    now: u64,
+) -> Result<(), ContractError> {
+    if !user.borrowed_amount.is_zero() {
+        match user.rate_mode {
+            RateMode::Variable => {
+                user.borrowed_amount = mul_ratio(
+                    user.borrowed_amount,
+                    pool.borrow_index.atomics(),
+                    user.borrow_index.atomics(),
+                    Rounding::Up,
+                )?;
+            }
+            RateMode::Stable => {
+                let elapsed = now.saturating_sub(user.last_interaction);
+                let interest = accrued_interest(user.borrowed_amount, user.stable_rate, elapsed)?;
+                user.borrowed_amount = user.borrowed_amount.checked_add(interest)?;
+                pool.stable_weighted_rate = pool
+                    .stable_weighted_rate
+                    .checked_add(interest.checked_mul(user.stable_rate)?)?;
+            }
+        }
+    }
+    user.borrow_index = pool.borrow_index;
+    user.last_interaction = now;
+    Ok(())
+}
+
+/// Adds open-ended debt to an accrued `user`. New stable debt is taken at
+/// `stable_rate` and blended with the rate already locked on existing debt,
+/// rounding the blended rate up.
+pub fn add_debt(
+    pool: &mut PoolInfo,
+    user: &mut UserInfo,
+    amount: Uint128,
+    rate_mode: RateMode,
+    stable_rate: Uint128,
+) -> Result<(), ContractError> {
+    if rate_mode != user.rate_mode && !user.borrowed_amount.is_zero() {
+        return Err(ContractError::RateModeMismatch {});
+    }
+
+    let new_debt = user.borrowed_amount.checked_add(amount)?;
+    match rate_mode {
+        RateMode::Variable => {
+            pool.variable_borrowed = pool.variable_borrowed.checked_add(amount)?
+        }
+        RateMode::Stable => {
+            let old_weight = user.borrowed_amount.checked_mul(user.stable_rate)?;
+            let weight = old_weight.checked_add(amount.checked_mul(stable_rate)?)?;
+            user.stable_rate = mul_ratio(weight, 1u128, new_debt, Rounding::Up)?;
+            pool.stable_borrowed = pool.stable_borrowed.checked_add(amount)?;
+            pool.stable_weighted_rate = sub_pool_total(pool.stable_weighted_rate, old_weight)
+                .checked_add(new_debt.checked_mul(user.stable_rate)?)?;
+        }
+    }
+
+    user.rate_mode = rate_mode;
+    user.borrowed_amount = new_debt;
+    pool.total_borrowed = pool.total_borrowed.checked_add(amount)?;
+    Ok(())
+}
+
+/// Removes open-ended debt from an accrued `user`. The pool totals can sit a
+/// few units below the sum of user debts, see `sub_pool_total`.
+pub fn remove_debt(
+    pool: &mut PoolInfo,
+    user: &mut UserInfo,
+    amount: Uint128,
+) -> Result<(), ContractError> {
+    if amount > user.borrowed_amount {
+        return Err(ContractError::ExcessRepayment {});
+    }
+
+    user.borrowed_amount = user.borrowed_amount.checked_sub(amount)?;
+    match user.rate_mode {
+        RateMode::Variable => {
+            pool.variable_borrowed = sub_pool_total(pool.variable_borrowed, amount)
+        }
+        RateMode::Stable => {
+            pool.stable_borrowed = sub_pool_total(pool.stable_borrowed, amount);
+            pool.stable_weighted_rate = sub_pool_total(
+                pool.stable_weighted_rate,
+                amount.checked_mul(user.stable_rate)?,
+            );
+        }
+    }
+    pool.total_borrowed = sub_pool_total(pool.total_borrowed, amount);
+    Ok(())
+}
+
+/// Total amount owed on a fixed-term loan at `now`, including the penalty
+/// rate for any time past maturity.
+pub fn loan_amount_owed(config: &Config, loan: &Loan, now: u64) -> Result<Uint128, ContractError> {
+    let on_time = now.min(loan.maturity).saturating_sub(loan.start_time);
+    let overdue = now.saturating_sub(loan.maturity);
+    let penalty_rate = loan.rate.checked_add(config.penalty_rate)?;
+
+    Ok(loan
+        .principal
+        .checked_add(accrued_interest(loan.principal, loan.rate, on_time)?)?
+        .checked_add(accrued_interest(loan.principal, penalty_rate, overdue)?)?)
+}
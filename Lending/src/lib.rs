@@ -1,21 +1,66 @@
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    entry_point, to_json_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult,
-    Uint128, WasmMsg, Addr, from_json, StdError, to_binary,
+    to_json_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult,
+    Uint128, WasmMsg, Addr, from_json, Empty, Event, Order, Decimal, Reply, SubMsg, Storage,
 };
-use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
+use cw20::{
+    AllowanceResponse, BalanceResponse, Cw20Coin, Cw20ExecuteMsg, Cw20QueryMsg, Cw20ReceiveMsg,
+    Expiration, MinterResponse, TokenInfoResponse,
+};
+use cw_storage_plus::Bound;
+use cw_utils::parse_reply_instantiate_data;
+use token::msg::{BlockedResponse, ComplianceResponse, Permit, QueryMsg as TokenQueryMsg};
 
-mod error;
-mod msg;
-mod state;
+pub mod error;
+mod events;
+mod interest;
+mod math;
+pub mod msg;
+mod rewards;
+pub mod state;
+#[cfg(test)]
+mod tests;
 
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
-use crate::state::{Config, UserInfo, PoolInfo, CONFIG, USERS, POOL};
+use crate::events::{admin_event, position_event};
+use crate::msg::{
+    AuctionResponse, ConfigChange, ConfigResponse, Cw20HookMsg, ExecuteMsg, InstantiateMsg,
+    LoanResponse, LockResponse, MigrateMsg, PendingChangeResponse, PendingRewardsResponse,
+    PoolInfoResponse, QueryMsg, RateMode, RatesResponse, SimulationResponse, UnbondingResponse,
+    UserInfoResponse,
+};
+use crate::interest::{
+    accrue_pool, accrue_user, add_debt, average_stable_rate, borrow_limit, loan_amount_owed,
+    remove_debt, total_debt, utilization, variable_rate,
+};
+use crate::math::{add_seconds, mul_ratio, ratio, sub_pool_total, Rounding};
+use crate::state::{
+    Config, Loan, Lock, UserInfo, PoolInfo, RewardState, CONFIG, USERS, POOL,
+    LOAN_COUNT, LOANS, USER_LOANS, DEBT_ALLOWANCES, REWARD_STATE, USER_REWARDS, LOCK_COUNT,
+    LOCKS, LOCK_EXPIRIES, LockExpiry, Unbonding, UNBONDING_COUNT, UNBONDING, PendingChange,
+    PENDING_CHANGE_COUNT, PENDING_CHANGES, Auction, AUCTION_COUNT, AUCTIONS, UNCLAIMED_PAYOUTS,
+    UNCLAIMED_TOTAL, RewardEpoch, UserRewards, REWARD_EPOCHS, SETTLED_REWARDS, REWARD_RESERVES,
+};
+use crate::rewards::{update_reward_indices, update_user_rewards};
 
-#[entry_point]
+const INSTANTIATE_RECEIPT_REPLY_ID: u64 = 1;
+const RECEIPT_NAME: &str = "Lending Pool USD Receipt";
+const RECEIPT_SYMBOL: &str = "lpUSD";
+const RECEIPT_DECIMALS: u8 = 6;
+const DEBT_NAME: &str = "Lending Pool OM Debt";
+const DEBT_SYMBOL: &str = "debtOM";
+const DEBT_DECIMALS: u8 = 6;
+
+const DAY: u64 = 86_400;
+/// Accepted `StakeLocked` durations in seconds, with their reward multiplier
+/// in percent.
+const LOCK_TIERS: [(u64, u128); 3] = [(30 * DAY, 110), (90 * DAY, 125), (180 * DAY, 150)];
+
+#[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
@@ -25,19 +70,86 @@ pub fn instantiate(
         om_token: deps.api.addr_validate(&msg.om_token)?,
         collateral_ratio: msg.collateral_ratio,
         interest_rate: msg.interest_rate,
+        penalty_rate: msg.penalty_rate,
+        rate_slope: msg.rate_slope,
+        rebalance_utilization: msg.rebalance_utilization,
+        unbonding_period: msg.unbonding_period,
+        guardian: deps.api.addr_validate(&msg.guardian)?,
+        timelock_delay: msg.timelock_delay,
+        auction_start_price: msg.auction_start_price,
+        auction_step: msg.auction_step,
+        liquidation_bonus: msg.liquidation_bonus,
+        close_factor: msg.close_factor,
+        receipt_token: None,
     };
+    config.validate()?;
     CONFIG.save(deps.storage, &config)?;
+    LOAN_COUNT.save(deps.storage, &0)?;
+    LOCK_COUNT.save(deps.storage, &0)?;
+    UNBONDING_COUNT.save(deps.storage, &0)?;
+    PENDING_CHANGE_COUNT.save(deps.storage, &0)?;
+    AUCTION_COUNT.save(deps.storage, &0)?;
 
     let pool = PoolInfo {
         total_staked: Uint128::zero(),
         total_borrowed: Uint128::zero(),
+        total_boost: Uint128::zero(),
+        variable_borrowed: Uint128::zero(),
+        stable_borrowed: Uint128::zero(),
+        stable_weighted_rate: Uint128::zero(),
+        borrow_index: Decimal::one(),
+        last_accrual: env.block.time.seconds(),
     };
     POOL.save(deps.storage, &pool)?;
 
-    Ok(Response::new().add_attribute("method", "instantiate"))
+    // The receipt token is a `token` contract with this pool as both minter and
+    // controller, so every receipt transfer is checked against collateral health.
+    let receipt_msg = token::msg::InstantiateMsg {
+        base: cw20_base::msg::InstantiateMsg {
+            name: RECEIPT_NAME.to_string(),
+            symbol: RECEIPT_SYMBOL.to_string(),
+            decimals: RECEIPT_DECIMALS,
+            initial_balances: vec![],
+            mint: Some(MinterResponse {
+                minter: env.contract.address.to_string(),
+                cap: None,
+            }),
+            marketing: None,
+        },
+        controller: Some(env.contract.address.to_string()),
+    };
+    let instantiate_receipt = WasmMsg::Instantiate {
+        admin: Some(info.sender.to_string()),
+        code_id: msg.receipt_code_id,
+        msg: to_json_binary(&receipt_msg)?,
+        funds: vec![],
+        label: RECEIPT_NAME.to_string(),
+    };
+
+    Ok(Response::new()
+        .add_submessage(SubMsg::reply_on_success(instantiate_receipt, INSTANTIATE_RECEIPT_REPLY_ID))
+        .add_attribute("method", "instantiate"))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
+    match msg.id {
+        INSTANTIATE_RECEIPT_REPLY_ID => {
+            let res = parse_reply_instantiate_data(msg)?;
+            let receipt_token = deps.api.addr_validate(&res.contract_address)?;
+            CONFIG.update(deps.storage, |mut config| -> StdResult<_> {
+                config.receipt_token = Some(receipt_token.clone());
+                Ok(config)
+            })?;
+            Ok(Response::new()
+                .add_attribute("method", "reply_instantiate_receipt")
+                .add_attribute("receipt_token", receipt_token))
+        }
+        id => Err(ContractError::UnknownReplyId { id }),
+    }
 }
 
-#[entry_point]
+#[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
     deps: DepsMut,
     env: Env,
@@ -45,10 +157,38 @@ pub fn execute(
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
     match msg {
-        ExecuteMsg::Stake {} => execute::stake(deps, env, info),
+        ExecuteMsg::StakeWithPermit { permit } => execute::stake_with_permit(deps, env, permit),
         ExecuteMsg::Unstake { amount } => execute::unstake(deps, env, info, amount),
-        ExecuteMsg::Borrow { amount } => execute::borrow(deps, env, info, amount),
-        ExecuteMsg::Repay {} => execute::repay(deps, env, info),
+        ExecuteMsg::ClaimUnbonded {} => execute::claim_unbonded(deps, env, info),
+        ExecuteMsg::Borrow { amount, rate_mode } => {
+            execute::borrow(deps, env, info, amount, rate_mode)
+        }
+        ExecuteMsg::BorrowFixed { amount, term_seconds } => {
+            execute::borrow_fixed(deps, env, info, amount, term_seconds)
+        }
+        ExecuteMsg::StartAuction { borrower } => execute::start_auction(deps, env, borrower),
+        ExecuteMsg::RebalanceStableRate { address } => {
+            execute::rebalance_stable_rate(deps, env, info, address)
+        }
+        ExecuteMsg::ReceiptTransfer { sender, recipient, amount } => {
+            execute::receipt_transfer(deps, env, info, sender, recipient, amount)
+        }
+        ExecuteMsg::ApproveDelegation { delegatee, amount } => {
+            execute::approve_delegation(deps, env, info, delegatee, amount)
+        }
+        ExecuteMsg::TransferDebt { recipient, amount } => {
+            execute::transfer_debt(deps, env, info, recipient, amount)
+        }
+        ExecuteMsg::UpdateRewards { reward_token, supply_rate, borrow_rate } => {
+            execute::update_rewards(deps, env, info, reward_token, supply_rate, borrow_rate)
+        }
+        ExecuteMsg::ClaimRewards {} => execute::claim_rewards(deps, env, info),
+        ExecuteMsg::QueueConfigChange { change } => {
+            execute::queue_config_change(deps, env, info, change)
+        }
+        ExecuteMsg::ExecuteConfigChange { id } => execute::execute_config_change(deps, env, id),
+        ExecuteMsg::CancelConfigChange { id } => execute::cancel_config_change(deps, env, info, id),
+        ExecuteMsg::ClaimPayout {} => execute::claim_payout(deps, env, info),
         ExecuteMsg::Receive(msg) => receive_cw20(deps, env, info, msg),
     }
 }
@@ -59,140 +199,2014 @@ pub fn receive_cw20(
     info: MessageInfo,
     cw20_msg: Cw20ReceiveMsg,
 ) -> Result<Response, ContractError> {
+    // `info.sender` is the token contract; the funds belong to `cw20_msg.sender`.
+    let config = CONFIG.load(deps.storage)?;
+    let sender = deps.api.addr_validate(&cw20_msg.sender)?;
     match from_json(&cw20_msg.msg) {
-        Ok(ExecuteMsg::Stake {}) => execute::stake(deps, env, info),
-        Ok(ExecuteMsg::Repay {}) => execute::repay(deps, env, info),
+        Ok(Cw20HookMsg::Stake { on_behalf_of }) if info.sender == config.usd_token => {
+            let beneficiary = hook_address(deps.as_ref(), on_behalf_of, &sender)?;
+            execute::deposit(deps, env, beneficiary, cw20_msg.amount)
+        }
+        Ok(Cw20HookMsg::StakeLocked { duration, on_behalf_of }) if info.sender == config.usd_token => {
+            let beneficiary = hook_address(deps.as_ref(), on_behalf_of, &sender)?;
+            execute::stake_locked(deps, env, beneficiary, cw20_msg.amount, duration)
+        }
+        Ok(Cw20HookMsg::Repay { on_behalf_of }) if info.sender == config.om_token => {
+            let borrower = hook_address(deps.as_ref(), on_behalf_of, &sender)?;
+            execute::repay_debt(deps, env, borrower, cw20_msg.amount)
+        }
+        Ok(Cw20HookMsg::Liquidate { borrower, recipient }) if info.sender == config.om_token => {
+            let recipient = hook_address(deps.as_ref(), recipient, &sender)?;
+            execute::liquidate(deps, env, sender, recipient, borrower, cw20_msg.amount)
+        }
+        Ok(Cw20HookMsg::RepayLoan { loan_id }) if info.sender == config.om_token => {
+            execute::repay_loan(deps, env, sender, loan_id, cw20_msg.amount)
+        }
+        Ok(Cw20HookMsg::LiquidateLoan { loan_id, recipient }) if info.sender == config.om_token => {
+            let recipient = hook_address(deps.as_ref(), recipient, &sender)?;
+            execute::liquidate_loan(deps, env, sender, recipient, loan_id, cw20_msg.amount)
+        }
+        Ok(Cw20HookMsg::Bid { auction_id, recipient }) if info.sender == config.om_token => {
+            let recipient = hook_address(deps.as_ref(), recipient, &sender)?;
+            execute::place_bid(deps, env, sender, recipient, cw20_msg.amount, auction_id)
+        }
+        Ok(Cw20HookMsg::FundRewards {}) => {
+            execute::fund_rewards(deps, env, info.sender, cw20_msg.amount)
+        }
         _ => Err(ContractError::InvalidCw20Hook {}),
     }
 }
 
-#[entry_point]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
+/// Validates an optional address from a CW20 hook, defaulting to the sender.
+fn hook_address(deps: Deps, address: Option<String>, sender: &Addr) -> StdResult<Addr> {
+    match address {
+        Some(address) => deps.api.addr_validate(&address),
+        None => Ok(sender.clone()),
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> Result<Binary, ContractError> {
+    let res = match msg {
         QueryMsg::GetConfig {} => to_json_binary(&query::config(deps)?),
-        QueryMsg::GetUserInfo { address } => to_json_binary(&query::user_info(deps, address)?),
-        QueryMsg::GetPoolInfo {} => to_json_binary(&query::pool_info(deps)?),
+        QueryMsg::GetUserInfo { address } => {
+            to_json_binary(&query::user_info(deps, env, address)?)
+        }
+        QueryMsg::GetPoolInfo {} => to_json_binary(&query::pool_info(deps, env)?),
+        QueryMsg::GetUserLocks { address } => to_json_binary(&query::user_locks(deps, address)?),
+        QueryMsg::GetUnbonding { address } => to_json_binary(&query::unbonding(deps, address)?),
+        QueryMsg::GetLoan { loan_id } => to_json_binary(&query::loan(deps, loan_id)?),
+        QueryMsg::GetUserLoans { address } => to_json_binary(&query::user_loans(deps, address)?),
+        QueryMsg::GetAuction { auction_id } => {
+            to_json_binary(&query::auction(deps, env, auction_id)?)
+        }
+        QueryMsg::GetAuctions {} => to_json_binary(&query::auctions(deps, env)?),
+        QueryMsg::GetRates {} => to_json_binary(&query::rates(deps)?),
+        QueryMsg::GetPendingRewards { address } => {
+            to_json_binary(&query::pending_rewards(deps, env, address)?)
+        }
+        QueryMsg::SimulateBorrow { address, amount, rate_mode } => {
+            to_json_binary(&query::simulate_borrow(deps, env, address, amount, rate_mode)?)
+        }
+        QueryMsg::SimulateUnstake { address, amount } => {
+            to_json_binary(&query::simulate_unstake(deps, env, address, amount)?)
+        }
+        QueryMsg::SimulateRepay { address, amount } => {
+            to_json_binary(&query::simulate_repay(deps, env, address, amount)?)
+        }
+        QueryMsg::SimulateLiquidation { borrower, amount } => {
+            to_json_binary(&query::simulate_liquidation(deps, env, borrower, amount)?)
+        }
+        QueryMsg::GetPendingChanges {} => to_json_binary(&query::pending_changes(deps)?),
+        QueryMsg::Balance { address } => to_json_binary(&query::debt_balance(deps, env, address)?),
+        QueryMsg::TokenInfo {} => to_json_binary(&query::debt_token_info(deps, env)?),
+        QueryMsg::Allowance { owner, spender } => {
+            to_json_binary(&query::debt_allowance(deps, owner, spender)?)
+        }
+        QueryMsg::GetUnclaimedPayout { address } => {
+            to_json_binary(&query::unclaimed_payout(deps, address)?)
+        }
+    };
+    Ok(res?)
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    // Locks opened before expiries were tracked.
+    let untracked = LOCK_EXPIRIES
+        .keys(deps.storage, None, None, Order::Ascending)
+        .next()
+        .is_none();
+    if untracked {
+        let locks = LOCKS
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        for (_, lock) in locks {
+            let mut expiry = LOCK_EXPIRIES.may_load(deps.storage, lock.unlock_time)?.unwrap_or_default();
+            expiry.boost = expiry.boost.checked_add(lock.boost()?)?;
+            expiry.locks += 1;
+            LOCK_EXPIRIES.save(deps.storage, lock.unlock_time, &expiry)?;
+        }
     }
+    Ok(Response::default())
 }
 
 mod execute {
     use super::*;
 
-    pub fn stake(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+    /// Submits the permit to the USD token and pulls the permitted amount
+    /// before crediting it, all in this transaction.
+    pub fn stake_with_permit(deps: DepsMut, env: Env, permit: Permit) -> Result<Response, ContractError> {
+        let config = CONFIG.load(deps.storage)?;
+        let lending = env.contract.address.clone();
+        if permit.spender != lending.as_str() {
+            return Err(ContractError::InvalidPermit {});
+        }
+        let owner = deps.api.addr_validate(&permit.owner)?;
+        let amount = permit.amount;
+
+        let permit = WasmMsg::Execute {
+            contract_addr: config.usd_token.to_string(),
+            msg: to_json_binary(&token::msg::ExecuteMsg::Permit(permit))?,
+            funds: vec![],
+        };
+        let transfer = WasmMsg::Execute {
+            contract_addr: config.usd_token.to_string(),
+            msg: to_json_binary(&Cw20ExecuteMsg::TransferFrom {
+                owner: owner.to_string(),
+                recipient: lending.to_string(),
+                amount,
+            })?,
+            funds: vec![],
+        };
+
+        let mut res = deposit(deps, env, owner, amount)?;
+        res.messages.splice(0..0, [SubMsg::new(permit), SubMsg::new(transfer)]);
+        Ok(res)
+    }
+
+    pub fn deposit(deps: DepsMut, env: Env, sender: Addr, amount: Uint128) -> Result<Response, ContractError> {
         let config = CONFIG.load(deps.storage)?;
         let mut pool = POOL.load(deps.storage)?;
-        let amount = info.funds.iter().find(|c| c.denom == config.usd_token.to_string())
-            .map(|c| c.amount)
-            .ok_or(ContractError::NoFunds {})?;
+        let mut user = USERS.may_load(deps.storage, &sender)?.unwrap_or_default();
 
-        let mut user = USERS.may_load(deps.storage, &info.sender)?.unwrap_or_default();
-        user.staked_amount += amount;
-        USERS.save(deps.storage, &info.sender, &user)?;
+        let now = env.block.time.seconds();
+        checkpoint_rewards(deps.storage, &mut pool, &sender, &user, now)?;
+        accrue_pool(&config, &mut pool, now)?;
 
-        pool.total_staked += amount;
+        user.staked_amount = user.staked_amount.checked_add(amount)?;
+        USERS.save(deps.storage, &sender, &user)?;
+
+        pool.total_staked = pool.total_staked.checked_add(amount)?;
         POOL.save(deps.storage, &pool)?;
 
-        Ok(Response::new().add_attribute("action", "stake"))
+        let mint = WasmMsg::Execute {
+            contract_addr: receipt_token(&config)?.to_string(),
+            msg: to_json_binary(&Cw20ExecuteMsg::Mint {
+                recipient: sender.to_string(),
+                amount,
+            })?,
+            funds: vec![],
+        };
+
+        Ok(Response::new()
+            .add_message(mint)
+            .add_event(position_event("stake", &sender, &config.usd_token, amount, &user, &pool, now))
+            .add_attribute("action", "stake")
+            .add_attribute("amount", amount.to_string()))
     }
 
-    pub fn unstake(deps: DepsMut, _env: Env, info: MessageInfo, amount: Uint128) -> Result<Response, ContractError> {
+    /// Deposits like `deposit`, but the deposit cannot be withdrawn or
+    /// transferred until `duration` has passed. In return it earns boosted
+    /// supply rewards.
+    pub fn stake_locked(
+        mut deps: DepsMut,
+        env: Env,
+        sender: Addr,
+        amount: Uint128,
+        duration: u64,
+    ) -> Result<Response, ContractError> {
+        let multiplier = LOCK_TIERS
+            .iter()
+            .find(|(tier, _)| *tier == duration)
+            .map(|(_, multiplier)| Uint128::from(*multiplier))
+            .ok_or(ContractError::InvalidLockDuration {})?;
+
         let config = CONFIG.load(deps.storage)?;
+        let res = deposit(deps.branch(), env.clone(), sender.clone(), amount)?;
+
+        let id = LOCK_COUNT.load(deps.storage)? + 1;
+        LOCK_COUNT.save(deps.storage, &id)?;
+
+        let lock = Lock {
+            id,
+            amount,
+            multiplier,
+            unlock_time: add_seconds(env.block.time.seconds(), duration)?,
+        };
+        LOCKS.save(deps.storage, (&sender, id), &lock)?;
+
+        let boost = lock.boost()?;
+        let mut expiry = LOCK_EXPIRIES.may_load(deps.storage, lock.unlock_time)?.unwrap_or_default();
+        expiry.boost = expiry.boost.checked_add(boost)?;
+        expiry.locks += 1;
+        LOCK_EXPIRIES.save(deps.storage, lock.unlock_time, &expiry)?;
+        let mut user = load_user(deps.storage, &sender)?;
+        user.locked_amount = user.locked_amount.checked_add(amount)?;
+        user.boosted_amount = user.boosted_amount.checked_add(boost)?;
+        USERS.save(deps.storage, &sender, &user)?;
+
         let mut pool = POOL.load(deps.storage)?;
-        let mut user = USERS.load(deps.storage, &info.sender)?;
+        pool.total_boost = pool.total_boost.checked_add(boost)?;
+        POOL.save(deps.storage, &pool)?;
+
+        let event = position_event(
+            "stake_locked",
+            &sender,
+            &config.usd_token,
+            amount,
+            &user,
+            &pool,
+            env.block.time.seconds(),
+        )
+        .add_attribute("lock_id", id.to_string())
+        .add_attribute("unlock_time", lock.unlock_time.to_string());
+
+        Ok(res
+            .add_event(event)
+            .add_attribute("lock_id", id.to_string())
+            .add_attribute("unlock_time", lock.unlock_time.to_string()))
+    }
+
+    pub fn unstake(deps: DepsMut, env: Env, info: MessageInfo, amount: Uint128) -> Result<Response, ContractError> {
+        let config = CONFIG.load(deps.storage)?;
+        let mut pool = POOL.load(deps.storage)?;
+        let mut user = load_user(deps.storage, &info.sender)?;
+
+        let now = env.block.time.seconds();
+        checkpoint_rewards(deps.storage, &mut pool, &info.sender, &user, now)?;
+        let expired = expired_locks(deps.storage, &info.sender, now)?;
+        apply_unstake(&config, &mut pool, &mut user, &expired, amount, now)?;
+        for (id, lock) in expired {
+            remove_lock(deps.storage, &info.sender, id, &lock)?;
+        }
+
+        USERS.save(deps.storage, &info.sender, &user)?;
+        POOL.save(deps.storage, &pool)?;
+
+        let res = Response::new()
+            .add_message(burn_receipt_msg(&config, &info.sender, amount)?)
+            .add_event(position_event("unstake", &info.sender, &config.usd_token, amount, &user, &pool, now))
+            .add_attribute("action", "unstake")
+            .add_attribute("amount", amount.to_string());
+
+        if config.unbonding_period == 0 {
+            return Ok(res.add_message(transfer_msg(&config.usd_token, &info.sender, amount)?));
+        }
+
+        // The deposit stops counting as collateral and liquidity right away,
+        // but is only paid out by `claim_unbonded` once the period has passed.
+        let id = UNBONDING_COUNT.load(deps.storage)? + 1;
+        UNBONDING_COUNT.save(deps.storage, &id)?;
+        let unbonding = Unbonding {
+            id,
+            amount,
+            release_time: add_seconds(now, config.unbonding_period)?,
+        };
+        UNBONDING.save(deps.storage, (&info.sender, id), &unbonding)?;
+
+        Ok(res
+            .add_attribute("unbonding_id", id.to_string())
+            .add_attribute("release_time", unbonding.release_time.to_string()))
+    }
+
+    /// Validation and accounting for `unstake`, shared with `SimulateUnstake`.
+    /// `expired` are the position's locks that have run out by `now`.
+    pub fn apply_unstake(
+        config: &Config,
+        pool: &mut PoolInfo,
+        user: &mut UserInfo,
+        expired: &[(u64, Lock)],
+        amount: Uint128,
+        now: u64,
+    ) -> Result<(), ContractError> {
+        accrue_pool(config, pool, now)?;
+        accrue_user(pool, user, now)?;
+        release_locks(user, expired)?;
 
         if user.staked_amount < amount {
             return Err(ContractError::InsufficientFunds {});
         }
+        if user.staked_amount - user.locked_amount.min(user.staked_amount) < amount {
+            return Err(ContractError::FundsLocked {});
+        }
+
+        user.staked_amount = user.staked_amount.checked_sub(amount)?;
+        if !is_healthy(config, user)? {
+            return Err(ContractError::ExceedsCollateralRatio {});
+        }
+        pool.total_staked = pool.total_staked.checked_sub(amount)?;
+        Ok(())
+    }
+
+    pub fn claim_unbonded(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+        let config = CONFIG.load(deps.storage)?;
+        let now = env.block.time.seconds();
+
+        let matured = UNBONDING
+            .prefix(&info.sender)
+            .range(deps.storage, None, None, Order::Ascending)
+            .filter(|item| match item {
+                Ok((_, unbonding)) => unbonding.release_time <= now,
+                Err(_) => true,
+            })
+            .collect::<StdResult<Vec<_>>>()?;
+
+        let mut amount = Uint128::zero();
+        for (id, unbonding) in matured {
+            amount = amount.checked_add(unbonding.amount)?;
+            UNBONDING.remove(deps.storage, (&info.sender, id));
+        }
+        if amount.is_zero() {
+            return Err(ContractError::NothingToClaim {});
+        }
+
+        let pool = POOL.load(deps.storage)?;
+        let user = USERS.may_load(deps.storage, &info.sender)?.unwrap_or_default();
+
+        Ok(Response::new()
+            .add_message(transfer_msg(&config.usd_token, &info.sender, amount)?)
+            .add_event(position_event(
+                "claim_unbonded",
+                &info.sender,
+                &config.usd_token,
+                amount,
+                &user,
+                &pool,
+                now,
+            ))
+            .add_attribute("action", "claim_unbonded")
+            .add_attribute("amount", amount.to_string()))
+    }
+
+    pub fn borrow(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        amount: Uint128,
+        rate_mode: Option<RateMode>,
+    ) -> Result<Response, ContractError> {
+        let config = CONFIG.load(deps.storage)?;
+        let mut pool = POOL.load(deps.storage)?;
+        let mut user = load_user(deps.storage, &info.sender)?;
+
+        let now = env.block.time.seconds();
+        check_liquidity(deps.as_ref(), &env.contract.address, &config, amount)?;
+        checkpoint_rewards(deps.storage, &mut pool, &info.sender, &user, now)?;
+        let rate_mode = apply_borrow(&config, &mut pool, &mut user, amount, rate_mode, now)?;
+
+        USERS.save(deps.storage, &info.sender, &user)?;
+        POOL.save(deps.storage, &pool)?;
+
+        let event = position_event("borrow", &info.sender, &config.om_token, amount, &user, &pool, now)
+            .add_attribute("rate_mode", rate_mode.to_string());
+
+        Ok(payout(deps, &config, &info.sender, amount, now)?
+            .add_event(event)
+            .add_attribute("action", "borrow")
+            .add_attribute("amount", amount.to_string())
+            .add_attribute("rate_mode", rate_mode.to_string()))
+    }
+
+    /// Validation and accounting for `borrow`, shared with `SimulateBorrow`.
+    /// Returns the rate mode the debt ends up in.
+    pub fn apply_borrow(
+        config: &Config,
+        pool: &mut PoolInfo,
+        user: &mut UserInfo,
+        amount: Uint128,
+        rate_mode: Option<RateMode>,
+        now: u64,
+    ) -> Result<RateMode, ContractError> {
+        if amount.is_zero() {
+            return Err(ContractError::InvalidZeroAmount {});
+        }
+
+        accrue_pool(config, pool, now)?;
+        accrue_user(pool, user, now)?;
+
+        if total_debt(user)?.checked_add(amount)? > borrow_limit(config, user)? {
+            return Err(ContractError::ExceedsCollateralRatio {});
+        }
+
+        let rate_mode = rate_mode.unwrap_or(user.rate_mode);
+        let rate = variable_rate(config, pool)?;
+        add_debt(pool, user, amount, rate_mode, rate)?;
+        Ok(rate_mode)
+    }
+
+    pub fn repay_debt(
+        deps: DepsMut,
+        env: Env,
+        borrower: Addr,
+        repay_amount: Uint128,
+    ) -> Result<Response, ContractError> {
+        let config = CONFIG.load(deps.storage)?;
+        let mut pool = POOL.load(deps.storage)?;
+        let mut user = load_user(deps.storage, &borrower)?;
+
+        let now = env.block.time.seconds();
+        checkpoint_rewards(deps.storage, &mut pool, &borrower, &user, now)?;
+        apply_repay(&config, &mut pool, &mut user, repay_amount, now)?;
+
+        USERS.save(deps.storage, &borrower, &user)?;
+        POOL.save(deps.storage, &pool)?;
+
+        Ok(Response::new()
+            .add_event(position_event("repay", &borrower, &config.om_token, repay_amount, &user, &pool, now))
+            .add_attribute("action", "repay")
+            .add_attribute("borrower", borrower.to_string())
+            .add_attribute("amount", repay_amount.to_string()))
+    }
+
+    /// Validation and accounting for `repay_debt`, shared with `SimulateRepay`.
+    pub fn apply_repay(
+        config: &Config,
+        pool: &mut PoolInfo,
+        user: &mut UserInfo,
+        amount: Uint128,
+        now: u64,
+    ) -> Result<(), ContractError> {
+        accrue_pool(config, pool, now)?;
+        accrue_user(pool, user, now)?;
+        remove_debt(pool, user, amount)
+    }
+
+    pub fn liquidate(
+        deps: DepsMut,
+        env: Env,
+        liquidator: Addr,
+        recipient: Addr,
+        borrower: String,
+        amount: Uint128,
+    ) -> Result<Response, ContractError> {
+        let config = CONFIG.load(deps.storage)?;
+        let borrower = deps.api.addr_validate(&borrower)?;
+        let mut pool = POOL.load(deps.storage)?;
+        let mut user = load_user(deps.storage, &borrower)?;
+
+        let now = env.block.time.seconds();
+        checkpoint_rewards(deps.storage, &mut pool, &borrower, &user, now)?;
+        release_expired_locks(deps.storage, &borrower, &mut user, now)?;
+        let Liquidation { repaid, seized, locks_forfeited } =
+            apply_liquidation(&config, &mut pool, &mut user, amount, now)?;
+        if locks_forfeited {
+            remove_locks(deps.storage, &borrower)?;
+        }
+
+        USERS.save(deps.storage, &borrower, &user)?;
+        POOL.save(deps.storage, &pool)?;
+
+        let event = position_event("liquidate", &borrower, &config.om_token, repaid, &user, &pool, now)
+            .add_attribute("liquidator", liquidator.to_string())
+            .add_attribute("seized", seized.to_string());
+
+        let mut res = Response::new()
+            .add_event(event)
+            .add_attribute("action", "liquidate")
+            .add_attribute("borrower", borrower.to_string())
+            .add_attribute("amount", repaid.to_string())
+            .add_attribute("seized", seized.to_string());
+        if !seized.is_zero() {
+            res = res
+                .add_message(burn_receipt_msg(&config, &borrower, seized)?)
+                .add_message(transfer_msg(&config.usd_token, &recipient, seized)?);
+        }
+        if amount > repaid {
+            res = res.add_message(transfer_msg(&config.om_token, &liquidator, amount - repaid)?);
+        }
+        Ok(res)
+    }
+
+    /// Validation and accounting for `liquidate`, shared with
+    /// `SimulateLiquidation`.
+    pub fn apply_liquidation(
+        config: &Config,
+        pool: &mut PoolInfo,
+        user: &mut UserInfo,
+        amount: Uint128,
+        now: u64,
+    ) -> Result<Liquidation, ContractError> {
+        accrue_pool(config, pool, now)?;
+        accrue_user(pool, user, now)?;
+
+        if is_healthy(config, user)? {
+            return Err(ContractError::PositionHealthy {});
+        }
+        if user.borrowed_amount.is_zero() {
+            return Err(ContractError::NoDebt {});
+        }
+
+        // The close factor rounds up so dust positions can still be closed.
+        let max_repaid = mul_ratio(user.borrowed_amount, config.close_factor, 100u128, Rounding::Up)?;
+        let repaid = amount.min(max_repaid);
+        remove_debt(pool, user, repaid)?;
+
+        // The liquidator is paid out of the borrower's collateral, with the
+        // bonus on top.
+        let bonus = Uint128::new(100).checked_add(config.liquidation_bonus)?;
+        let seized = mul_ratio(repaid, bonus, 100u128, Rounding::Down)?.min(user.staked_amount);
+        user.staked_amount = user.staked_amount.checked_sub(seized)?;
+        pool.total_staked = pool.total_staked.checked_sub(seized)?;
+
+        let locks_forfeited = user.locked_amount > user.staked_amount;
+        if locks_forfeited {
+            clear_boost(pool, user);
+        }
+        Ok(Liquidation { repaid, seized, locks_forfeited })
+    }
+
+    pub struct Liquidation {
+        pub repaid: Uint128,
+        pub seized: Uint128,
+        /// The position's locks must be removed from storage.
+        pub locks_forfeited: bool,
+    }
+
+    pub fn borrow_fixed(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        amount: Uint128,
+        term_seconds: u64,
+    ) -> Result<Response, ContractError> {
+        if term_seconds == 0 {
+            return Err(ContractError::InvalidTerm {});
+        }
 
-        user.staked_amount -= amount;
+        let config = CONFIG.load(deps.storage)?;
+        let mut pool = POOL.load(deps.storage)?;
+        let mut user = load_user(deps.storage, &info.sender)?;
+
+        let now = env.block.time.seconds();
+        check_liquidity(deps.as_ref(), &env.contract.address, &config, amount)?;
+        checkpoint_rewards(deps.storage, &mut pool, &info.sender, &user, now)?;
+        accrue_pool(&config, &mut pool, now)?;
+        accrue_user(&mut pool, &mut user, now)?;
+
+        if total_debt(&user)?.checked_add(amount)? > borrow_limit(&config, &user)? {
+            return Err(ContractError::ExceedsCollateralRatio {});
+        }
+
+        let id = LOAN_COUNT.load(deps.storage)? + 1;
+        LOAN_COUNT.save(deps.storage, &id)?;
+
+        let loan = Loan {
+            id,
+            borrower: info.sender.clone(),
+            principal: amount,
+            rate: variable_rate(&config, &pool)?,
+            start_time: now,
+            maturity: add_seconds(now, term_seconds)?,
+        };
+        LOANS.save(deps.storage, id, &loan)?;
+        USER_LOANS.save(deps.storage, (&info.sender, id), &Empty {})?;
+
+        user.fixed_borrowed = user.fixed_borrowed.checked_add(amount)?;
         USERS.save(deps.storage, &info.sender, &user)?;
 
-        pool.total_staked -= amount;
+        pool.total_borrowed = pool.total_borrowed.checked_add(amount)?;
         POOL.save(deps.storage, &pool)?;
 
-        let msg = WasmMsg::Execute {
-            contract_addr: config.usd_token.to_string(),
-            msg: to_binary(&Cw20ExecuteMsg::Transfer {
-                recipient: info.sender.to_string(),
-                amount,
-            })?,
-            funds: vec![],
+        let event = position_event("borrow_fixed", &info.sender, &config.om_token, amount, &user, &pool, now)
+            .add_attribute("loan_id", id.to_string())
+            .add_attribute("maturity", loan.maturity.to_string());
+
+        Ok(payout(deps, &config, &info.sender, amount, now)?
+            .add_event(event)
+            .add_attribute("action", "borrow_fixed")
+            .add_attribute("loan_id", id.to_string())
+            .add_attribute("amount", amount.to_string())
+            .add_attribute("rate", loan.rate.to_string())
+            .add_attribute("maturity", loan.maturity.to_string()))
+    }
+
+    /// `payer` pays `paid` OM towards loan `loan_id` and gets any excess back.
+    pub fn repay_loan(
+        mut deps: DepsMut,
+        env: Env,
+        payer: Addr,
+        loan_id: u64,
+        paid: Uint128,
+    ) -> Result<Response, ContractError> {
+        let config = CONFIG.load(deps.storage)?;
+        let loan = LOANS.load(deps.storage, loan_id)?;
+
+        let now = env.block.time.seconds();
+        let owed = loan_amount_owed(&config, &loan, now)?;
+        if paid < owed {
+            return Err(ContractError::InsufficientFunds {});
+        }
+
+        close_loan(deps.branch(), &config, &loan, now)?;
+
+        let pool = POOL.load(deps.storage)?;
+        let user = load_user(deps.storage, &loan.borrower)?;
+        let event = position_event("repay_loan", &loan.borrower, &config.om_token, owed, &user, &pool, now)
+            .add_attribute("loan_id", loan_id.to_string());
+
+        let mut res = Response::new()
+            .add_event(event)
+            .add_attribute("action", "repay_loan")
+            .add_attribute("loan_id", loan_id.to_string())
+            .add_attribute("amount", owed.to_string());
+        if paid > owed {
+            res = res.add_message(transfer_msg(&config.om_token, &payer, paid - owed)?);
+        }
+        Ok(res)
+    }
+
+    /// `liquidator` pays `paid` OM to close past-due loan `loan_id` and gets
+    /// any excess back; the seized collateral goes to `recipient`.
+    pub fn liquidate_loan(
+        mut deps: DepsMut,
+        env: Env,
+        liquidator: Addr,
+        recipient: Addr,
+        loan_id: u64,
+        paid: Uint128,
+    ) -> Result<Response, ContractError> {
+        let config = CONFIG.load(deps.storage)?;
+        let loan = LOANS.load(deps.storage, loan_id)?;
+
+        let now = env.block.time.seconds();
+        if now <= loan.maturity {
+            return Err(ContractError::LoanNotPastDue {});
+        }
+
+        let owed = loan_amount_owed(&config, &loan, now)?;
+        if paid < owed {
+            return Err(ContractError::InsufficientFunds {});
+        }
+
+        close_loan(deps.branch(), &config, &loan, now)?;
+
+        // The liquidator is made whole out of the borrower's collateral.
+        let mut pool = POOL.load(deps.storage)?;
+        let mut user = load_user(deps.storage, &loan.borrower)?;
+        release_expired_locks(deps.storage, &loan.borrower, &mut user, now)?;
+        let seized = owed.min(user.staked_amount);
+        user.staked_amount = user.staked_amount.checked_sub(seized)?;
+        pool.total_staked = pool.total_staked.checked_sub(seized)?;
+        if user.locked_amount > user.staked_amount {
+            forfeit_locks(deps.storage, &mut pool, &loan.borrower, &mut user)?;
+        }
+        USERS.save(deps.storage, &loan.borrower, &user)?;
+        POOL.save(deps.storage, &pool)?;
+
+        let event = position_event("liquidate_loan", &loan.borrower, &config.om_token, owed, &user, &pool, now)
+            .add_attribute("loan_id", loan_id.to_string())
+            .add_attribute("liquidator", liquidator.to_string())
+            .add_attribute("seized", seized.to_string());
+
+        let mut res = Response::new()
+            .add_event(event)
+            .add_attribute("action", "liquidate_loan")
+            .add_attribute("loan_id", loan_id.to_string())
+            .add_attribute("borrower", loan.borrower.to_string())
+            .add_attribute("amount", owed.to_string())
+            .add_attribute("seized", seized.to_string());
+        if !seized.is_zero() {
+            res = res
+                .add_message(burn_receipt_msg(&config, &loan.borrower, seized)?)
+                .add_message(transfer_msg(&config.usd_token, &recipient, seized)?);
+        }
+        if paid > owed {
+            res = res.add_message(transfer_msg(&config.om_token, &liquidator, paid - owed)?);
+        }
+        Ok(res)
+    }
+
+    pub fn start_auction(deps: DepsMut, env: Env, borrower: String) -> Result<Response, ContractError> {
+        let config = CONFIG.load(deps.storage)?;
+        let borrower = deps.api.addr_validate(&borrower)?;
+        let mut pool = POOL.load(deps.storage)?;
+        let mut user = load_user(deps.storage, &borrower)?;
+
+        let now = env.block.time.seconds();
+        checkpoint_rewards(deps.storage, &mut pool, &borrower, &user, now)?;
+        release_expired_locks(deps.storage, &borrower, &mut user, now)?;
+        accrue_pool(&config, &mut pool, now)?;
+        accrue_user(&mut pool, &mut user, now)?;
+
+        if is_healthy(&config, &user)? {
+            return Err(ContractError::PositionHealthy {});
+        }
+        let debt = user.borrowed_amount;
+        if debt.is_zero() {
+            return Err(ContractError::NoDebt {});
+        }
+
+        // The debt stops accruing but stays in `total_borrowed` until a bid
+        // repays it.
+        remove_debt(&mut pool, &mut user, debt)?;
+        pool.total_borrowed = pool.total_borrowed.checked_add(debt)?;
+
+        forfeit_locks(deps.storage, &mut pool, &borrower, &mut user)?;
+
+        let collateral = user.staked_amount.saturating_sub(user.fixed_borrowed);
+        user.staked_amount = user.staked_amount.checked_sub(collateral)?;
+        pool.total_staked = pool.total_staked.checked_sub(collateral)?;
+
+        USERS.save(deps.storage, &borrower, &user)?;
+        POOL.save(deps.storage, &pool)?;
+
+        let id = AUCTION_COUNT.load(deps.storage)? + 1;
+        AUCTION_COUNT.save(deps.storage, &id)?;
+        let auction = Auction {
+            id,
+            borrower: borrower.clone(),
+            debt,
+            collateral,
+            start_time: now,
+            start_price: config.auction_start_price,
+            step: config.auction_step,
         };
+        AUCTIONS.save(deps.storage, id, &auction)?;
+
+        let event = position_event("start_auction", &borrower, &config.om_token, debt, &user, &pool, now)
+            .add_attribute("auction_id", id.to_string())
+            .add_attribute("collateral", collateral.to_string());
+
+        let mut res = Response::new()
+            .add_event(event)
+            .add_attribute("action", "start_auction")
+            .add_attribute("auction_id", id.to_string())
+            .add_attribute("borrower", borrower.to_string())
+            .add_attribute("debt", debt.to_string())
+            .add_attribute("collateral", collateral.to_string());
+        if !collateral.is_zero() {
+            res = res.add_message(burn_receipt_msg(&config, &borrower, collateral)?);
+        }
+        Ok(res)
+    }
+
+    /// `bidder` pays `paid` OM and gets any excess back; the collateral goes
+    /// to `recipient`.
+    pub fn place_bid(
+        deps: DepsMut,
+        env: Env,
+        bidder: Addr,
+        recipient: Addr,
+        paid: Uint128,
+        auction_id: u64,
+    ) -> Result<Response, ContractError> {
+        let config = CONFIG.load(deps.storage)?;
+        let auction = AUCTIONS.load(deps.storage, auction_id)?;
+
+        if paid < auction.debt {
+            return Err(ContractError::InsufficientFunds {});
+        }
+
+        let now = env.block.time.seconds();
+        let mut pool = POOL.load(deps.storage)?;
+        accrue_pool(&config, &mut pool, now)?;
+        pool.total_borrowed = sub_pool_total(pool.total_borrowed, auction.debt);
+        POOL.save(deps.storage, &pool)?;
+        AUCTIONS.remove(deps.storage, auction_id);
+
+        let payout = auction.payout(now)?;
+        let surplus = auction.collateral.checked_sub(payout)?;
+
+        let user = USERS.may_load(deps.storage, &auction.borrower)?.unwrap_or_default();
+        let event = position_event("bid", &auction.borrower, &config.om_token, auction.debt, &user, &pool, now)
+            .add_attribute("auction_id", auction_id.to_string())
+            .add_attribute("bidder", bidder.to_string())
+            .add_attribute("payout", payout.to_string())
+            .add_attribute("surplus", surplus.to_string());
+
+        let mut res = Response::new()
+            .add_event(event)
+            .add_attribute("action", "bid")
+            .add_attribute("auction_id", auction_id.to_string())
+            .add_attribute("borrower", auction.borrower.to_string())
+            .add_attribute("amount", auction.debt.to_string())
+            .add_attribute("payout", payout.to_string())
+            .add_attribute("surplus", surplus.to_string());
+        if !payout.is_zero() {
+            res = res.add_message(transfer_msg(&config.usd_token, &recipient, payout)?);
+        }
+        if !surplus.is_zero() {
+            res = res.add_message(transfer_msg(&config.usd_token, &auction.borrower, surplus)?);
+        }
+        if paid > auction.debt {
+            res = res.add_message(transfer_msg(&config.om_token, &bidder, paid - auction.debt)?);
+        }
+        Ok(res)
+    }
+
+    fn close_loan(deps: DepsMut, config: &Config, loan: &Loan, now: u64) -> Result<(), ContractError> {
+        let mut pool = POOL.load(deps.storage)?;
+        let mut user = load_user(deps.storage, &loan.borrower)?;
+        checkpoint_rewards(deps.storage, &mut pool, &loan.borrower, &user, now)?;
+        accrue_pool(config, &mut pool, now)?;
+
+        user.fixed_borrowed = user.fixed_borrowed.checked_sub(loan.principal)?;
+        USERS.save(deps.storage, &loan.borrower, &user)?;
+
+        pool.total_borrowed = sub_pool_total(pool.total_borrowed, loan.principal);
+        POOL.save(deps.storage, &pool)?;
+
+        LOANS.remove(deps.storage, loan.id);
+        USER_LOANS.remove(deps.storage, (&loan.borrower, loan.id));
+        Ok(())
+    }
+
+    pub fn rebalance_stable_rate(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        address: String,
+    ) -> Result<Response, ContractError> {
+        let config = CONFIG.load(deps.storage)?;
+        if info.sender != config.owner {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        let address = deps.api.addr_validate(&address)?;
+        let mut pool = POOL.load(deps.storage)?;
+        let mut user = load_user(deps.storage, &address)?;
+
+        let now = env.block.time.seconds();
+        checkpoint_rewards(deps.storage, &mut pool, &address, &user, now)?;
+        accrue_pool(&config, &mut pool, now)?;
+        accrue_user(&mut pool, &mut user, now)?;
+
+        let threshold = Decimal::from_ratio(config.rebalance_utilization, 100u128);
+        if user.rate_mode != RateMode::Stable
+            || user.borrowed_amount.is_zero()
+            || utilization(&pool) < threshold
+        {
+            return Err(ContractError::RebalanceConditionNotMet {});
+        }
+
+        let rate = variable_rate(&config, &pool)?;
+        let old_weight = user.borrowed_amount.checked_mul(user.stable_rate)?;
+        pool.stable_weighted_rate = sub_pool_total(pool.stable_weighted_rate, old_weight)
+            .checked_add(user.borrowed_amount.checked_mul(rate)?)?;
+        user.stable_rate = rate;
+
+        USERS.save(deps.storage, &address, &user)?;
+        POOL.save(deps.storage, &pool)?;
+
+        let event = position_event(
+            "rebalance_stable_rate",
+            &address,
+            &config.om_token,
+            user.borrowed_amount,
+            &user,
+            &pool,
+            now,
+        )
+        .add_attribute("rate", rate.to_string());
 
         Ok(Response::new()
-            .add_message(msg)
-            .add_attribute("action", "unstake")
-            .add_attribute("amount", amount.to_string()))
+            .add_event(event)
+            .add_attribute("action", "rebalance_stable_rate")
+            .add_attribute("address", address.to_string())
+            .add_attribute("rate", rate.to_string()))
     }
 
-    pub fn borrow(deps: DepsMut, _env: Env, info: MessageInfo, amount: Uint128) -> Result<Response, ContractError> {
+    /// Called by the receipt token before a receipt transfer completes. Moves
+    /// the underlying deposit with it, rejecting transfers that would leave
+    /// the sender undercollateralized.
+    pub fn receipt_transfer(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        sender: String,
+        recipient: String,
+        amount: Uint128,
+    ) -> Result<Response, ContractError> {
         let config = CONFIG.load(deps.storage)?;
+        if info.sender != receipt_token(&config)? {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        let sender = deps.api.addr_validate(&sender)?;
+        let recipient = deps.api.addr_validate(&recipient)?;
         let mut pool = POOL.load(deps.storage)?;
-        let mut user = USERS.load(deps.storage, &info.sender)?;
+        let mut from = load_user(deps.storage, &sender)?;
+        let mut to = USERS.may_load(deps.storage, &recipient)?.unwrap_or_default();
+
+        let now = env.block.time.seconds();
+        checkpoint_rewards(deps.storage, &mut pool, &sender, &from, now)?;
+        checkpoint_rewards(deps.storage, &mut pool, &recipient, &to, now)?;
+        accrue_pool(&config, &mut pool, now)?;
+        accrue_user(&mut pool, &mut from, now)?;
+        release_expired_locks(deps.storage, &sender, &mut from, now)?;
 
-        let max_borrow = user.staked_amount.multiply_ratio(config.collateral_ratio, 100u128);
-        if user.borrowed_amount + amount > max_borrow {
+        if from.staked_amount < amount {
+            return Err(ContractError::InsufficientFunds {});
+        }
+        if from.staked_amount - from.locked_amount.min(from.staked_amount) < amount {
+            return Err(ContractError::FundsLocked {});
+        }
+        from.staked_amount = from.staked_amount.checked_sub(amount)?;
+        if !is_healthy(&config, &from)? {
             return Err(ContractError::ExceedsCollateralRatio {});
         }
+        USERS.save(deps.storage, &sender, &from)?;
 
-        user.borrowed_amount += amount;
-        USERS.save(deps.storage, &info.sender, &user)?;
+        to.staked_amount = to.staked_amount.checked_add(amount)?;
+        USERS.save(deps.storage, &recipient, &to)?;
 
-        pool.total_borrowed += amount;
         POOL.save(deps.storage, &pool)?;
 
-        let msg = WasmMsg::Execute {
-            contract_addr: config.om_token.to_string(),
-            msg: to_binary(&Cw20ExecuteMsg::Transfer {
-                recipient: info.sender.to_string(),
+        Ok(Response::new()
+            .add_events(transfer_events(
+                "receipt_transfer",
+                &config.usd_token,
                 amount,
-            })?,
-            funds: vec![],
-        };
+                (&sender, &from),
+                (&recipient, &to),
+                &pool,
+                now,
+            ))
+            .add_attribute("action", "receipt_transfer")
+            .add_attribute("sender", sender)
+            .add_attribute("recipient", recipient)
+            .add_attribute("amount", amount.to_string()))
+    }
+
+    /// Lets `delegatee` move up to `amount` of its debt onto the sender.
+    pub fn approve_delegation(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        delegatee: String,
+        amount: Uint128,
+    ) -> Result<Response, ContractError> {
+        let delegatee = deps.api.addr_validate(&delegatee)?;
+        DEBT_ALLOWANCES.save(deps.storage, (&info.sender, &delegatee), &amount)?;
+
+        let config = CONFIG.load(deps.storage)?;
+        let pool = POOL.load(deps.storage)?;
+        let user = USERS.may_load(deps.storage, &info.sender)?.unwrap_or_default();
+        let event = position_event(
+            "approve_delegation",
+            &info.sender,
+            &config.om_token,
+            amount,
+            &user,
+            &pool,
+            env.block.time.seconds(),
+        )
+        .add_attribute("delegatee", delegatee.to_string());
 
         Ok(Response::new()
-            .add_message(msg)
-            .add_attribute("action", "borrow")
+            .add_event(event)
+            .add_attribute("action", "approve_delegation")
+            .add_attribute("delegator", info.sender)
+            .add_attribute("delegatee", delegatee)
             .add_attribute("amount", amount.to_string()))
     }
 
-    pub fn repay(deps: DepsMut, _env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+    /// Debt is not freely transferable: the recipient must have approved the
+    /// sender and must stay healthy after taking the debt on.
+    pub fn transfer_debt(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        recipient: String,
+        amount: Uint128,
+    ) -> Result<Response, ContractError> {
+        if amount.is_zero() {
+            return Err(ContractError::InvalidZeroAmount {});
+        }
+
+        let recipient = deps.api.addr_validate(&recipient)?;
+        if recipient == info.sender {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        let allowance = DEBT_ALLOWANCES
+            .may_load(deps.storage, (&recipient, &info.sender))?
+            .unwrap_or_default();
+        if allowance < amount {
+            return Err(ContractError::InsufficientDelegation {});
+        }
+        DEBT_ALLOWANCES.save(deps.storage, (&recipient, &info.sender), &allowance.checked_sub(amount)?)?;
+
         let config = CONFIG.load(deps.storage)?;
         let mut pool = POOL.load(deps.storage)?;
-        let mut user = USERS.load(deps.storage, &info.sender)?;
+        let mut from = load_user(deps.storage, &info.sender)?;
+        let mut to = USERS.may_load(deps.storage, &recipient)?.unwrap_or_default();
 
-        let repay_amount = info.funds.iter().find(|c| c.denom == config.om_token.to_string())
-            .map(|c| c.amount)
-            .ok_or(ContractError::NoFunds {})?;
+        let now = env.block.time.seconds();
+        checkpoint_rewards(deps.storage, &mut pool, &info.sender, &from, now)?;
+        checkpoint_rewards(deps.storage, &mut pool, &recipient, &to, now)?;
+        accrue_pool(&config, &mut pool, now)?;
+        accrue_user(&mut pool, &mut from, now)?;
+        accrue_user(&mut pool, &mut to, now)?;
 
-        if repay_amount > user.borrowed_amount {
-            return Err(ContractError::ExcessRepayment {});
+        // Debt moves with its rate mode, and stable debt keeps its locked rate.
+        let rate_mode = from.rate_mode;
+        let rate = from.stable_rate;
+        remove_debt(&mut pool, &mut from, amount)?;
+        add_debt(&mut pool, &mut to, amount, rate_mode, rate)?;
+        if !is_healthy(&config, &to)? {
+            return Err(ContractError::ExceedsCollateralRatio {});
         }
 
-        user.borrowed_amount -= repay_amount;
-        USERS.save(deps.storage, &info.sender, &user)?;
+        USERS.save(deps.storage, &info.sender, &from)?;
+        USERS.save(deps.storage, &recipient, &to)?;
+        POOL.save(deps.storage, &pool)?;
 
-        pool.total_borrowed -= repay_amount;
+        Ok(Response::new()
+            .add_events(transfer_events(
+                "transfer_debt",
+                &config.om_token,
+                amount,
+                (&info.sender, &from),
+                (&recipient, &to),
+                &pool,
+                now,
+            ))
+            .add_attribute("action", "transfer_debt")
+            .add_attribute("sender", info.sender)
+            .add_attribute("recipient", recipient)
+            .add_attribute("amount", amount.to_string()))
+    }
+
+    pub fn update_rewards(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        reward_token: String,
+        supply_rate: Uint128,
+        borrow_rate: Uint128,
+    ) -> Result<Response, ContractError> {
+        let config = CONFIG.load(deps.storage)?;
+        if info.sender != config.owner {
+            return Err(ContractError::Unauthorized {});
+        }
+        let reward_token = deps.api.addr_validate(&reward_token)?;
+
+        // Settle emissions at the old rates before switching.
+        let mut pool = POOL.load(deps.storage)?;
+        let now = env.block.time.seconds();
+        accrue_pool(&config, &mut pool, now)?;
+        let mut state = REWARD_STATE.may_load(deps.storage)?;
+        expire_boosts(deps.storage, &mut pool, state.as_mut(), now)?;
         POOL.save(deps.storage, &pool)?;
+        let mut state = match state {
+            Some(mut state) => {
+                update_reward_indices(&mut state, &pool, now)?;
+                if state.reward_token != reward_token {
+                    // Close the epoch so what was earned so far is paid in
+                    // the old token.
+                    let epoch = RewardEpoch {
+                        reward_token: state.reward_token.clone(),
+                        supply_index: state.supply_index,
+                        borrow_index: state.borrow_index,
+                    };
+                    REWARD_EPOCHS.save(deps.storage, state.epoch, &epoch)?;
+                    state.epoch += 1;
+                    state.supply_index = Decimal::zero();
+                    state.borrow_index = Decimal::zero();
+                }
+                state
+            }
+            None => RewardState {
+                reward_token: reward_token.clone(),
+                supply_rate,
+                borrow_rate,
+                supply_index: Decimal::zero(),
+                borrow_index: Decimal::zero(),
+                last_update: now,
+                epoch: 0,
+            },
+        };
+        state.reward_token = reward_token;
+        state.supply_rate = supply_rate;
+        state.borrow_rate = borrow_rate;
+        REWARD_STATE.save(deps.storage, &state)?;
+
+        let event = admin_event("update_rewards", now)
+            .add_attribute("reward_token", state.reward_token.to_string())
+            .add_attribute("supply_rate", supply_rate.to_string())
+            .add_attribute("borrow_rate", borrow_rate.to_string());
 
         Ok(Response::new()
-            .add_attribute("action", "repay")
-            .add_attribute("amount", repay_amount.to_string()))
+            .add_event(event)
+            .add_attribute("action", "update_rewards")
+            .add_attribute("reward_token", state.reward_token)
+            .add_attribute("supply_rate", supply_rate.to_string())
+            .add_attribute("borrow_rate", borrow_rate.to_string()))
+    }
+
+    pub fn queue_config_change(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        change: Box<ConfigChange>,
+    ) -> Result<Response, ContractError> {
+        let config = CONFIG.load(deps.storage)?;
+        if info.sender != config.owner {
+            return Err(ContractError::Unauthorized {});
+        }
+        // Reject a bad change now rather than when it lands.
+        config.clone().apply(deps.api, &change)?;
+
+        let id = PENDING_CHANGE_COUNT.load(deps.storage)? + 1;
+        PENDING_CHANGE_COUNT.save(deps.storage, &id)?;
+        let now = env.block.time.seconds();
+        let eta = add_seconds(now, config.timelock_delay)?;
+        PENDING_CHANGES.save(deps.storage, id, &PendingChange { id, change: *change, eta })?;
+
+        let event = admin_event("queue_config_change", now)
+            .add_attribute("id", id.to_string())
+            .add_attribute("eta", eta.to_string());
+
+        Ok(Response::new()
+            .add_event(event)
+            .add_attribute("action", "queue_config_change")
+            .add_attribute("id", id.to_string())
+            .add_attribute("eta", eta.to_string()))
+    }
+
+    pub fn execute_config_change(deps: DepsMut, env: Env, id: u64) -> Result<Response, ContractError> {
+        let pending = PENDING_CHANGES.load(deps.storage, id)?;
+        let now = env.block.time.seconds();
+        if now < pending.eta {
+            return Err(ContractError::TimelockNotExpired { eta: pending.eta });
+        }
+        PENDING_CHANGES.remove(deps.storage, id);
+
+        // Accrue interest up to now under the old rate model.
+        let mut config = CONFIG.load(deps.storage)?;
+        let mut pool = POOL.load(deps.storage)?;
+        accrue_pool(&config, &mut pool, now)?;
+        POOL.save(deps.storage, &pool)?;
+
+        config.apply(deps.api, &pending.change)?;
+        CONFIG.save(deps.storage, &config)?;
+
+        Ok(Response::new()
+            .add_event(admin_event("execute_config_change", now).add_attribute("id", id.to_string()))
+            .add_attribute("action", "execute_config_change")
+            .add_attribute("id", id.to_string()))
+    }
+
+    pub fn cancel_config_change(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        id: u64,
+    ) -> Result<Response, ContractError> {
+        let config = CONFIG.load(deps.storage)?;
+        if info.sender != config.guardian && info.sender != config.owner {
+            return Err(ContractError::Unauthorized {});
+        }
+        PENDING_CHANGES.load(deps.storage, id)?;
+        PENDING_CHANGES.remove(deps.storage, id);
+
+        let event = admin_event("cancel_config_change", env.block.time.seconds())
+            .add_attribute("id", id.to_string());
+
+        Ok(Response::new()
+            .add_event(event)
+            .add_attribute("action", "cancel_config_change")
+            .add_attribute("id", id.to_string()))
+    }
+
+    pub fn claim_rewards(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+        let state = REWARD_STATE.may_load(deps.storage)?.ok_or(ContractError::NoRewards {})?;
+        let mut pool = POOL.load(deps.storage)?;
+        let user = USERS.may_load(deps.storage, &info.sender)?.unwrap_or_default();
+
+        let now = env.block.time.seconds();
+        checkpoint_rewards(deps.storage, &mut pool, &info.sender, &user, now)?;
+        POOL.save(deps.storage, &pool)?;
+
+        let mut claims = SETTLED_REWARDS
+            .prefix(&info.sender)
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        for (token, _) in &claims {
+            SETTLED_REWARDS.remove(deps.storage, (&info.sender, token));
+        }
+        let mut rewards = USER_REWARDS.load(deps.storage, &info.sender)?;
+        if !rewards.pending.is_zero() {
+            claims.push((state.reward_token.clone(), rewards.pending));
+            rewards.pending = Uint128::zero();
+            USER_REWARDS.save(deps.storage, &info.sender, &rewards)?;
+        }
+        if claims.is_empty() {
+            return Err(ContractError::NoRewards {});
+        }
+
+        let mut response = Response::new().add_attribute("action", "claim_rewards");
+        for (token, amount) in claims {
+            take_reward_reserve(deps.storage, &token, amount)?;
+            response = response
+                .add_message(transfer_msg(&token, &info.sender, amount)?)
+                .add_event(position_event(
+                    "claim_rewards",
+                    &info.sender,
+                    &token,
+                    amount,
+                    &user,
+                    &pool,
+                    now,
+                ))
+                .add_attribute("amount", amount.to_string());
+        }
+        Ok(response)
+    }
+
+    pub fn fund_rewards(
+        deps: DepsMut,
+        env: Env,
+        token: Addr,
+        amount: Uint128,
+    ) -> Result<Response, ContractError> {
+        if amount.is_zero() {
+            return Err(ContractError::InvalidZeroAmount {});
+        }
+        let reserve = REWARD_RESERVES
+            .may_load(deps.storage, &token)?
+            .unwrap_or_default()
+            .checked_add(amount)?;
+        REWARD_RESERVES.save(deps.storage, &token, &reserve)?;
+
+        let event = admin_event("fund_rewards", env.block.time.seconds())
+            .add_attribute("token", &token)
+            .add_attribute("amount", amount.to_string())
+            .add_attribute("reserve", reserve.to_string());
+
+        Ok(Response::new()
+            .add_event(event)
+            .add_attribute("action", "fund_rewards")
+            .add_attribute("token", token)
+            .add_attribute("amount", amount.to_string()))
+    }
+
+    /// Takes a claim of `amount` out of `token`'s funded reserve.
+    fn take_reward_reserve(
+        storage: &mut dyn Storage,
+        token: &Addr,
+        amount: Uint128,
+    ) -> Result<(), ContractError> {
+        let reserve = REWARD_RESERVES.may_load(storage, token)?.unwrap_or_default();
+        if amount > reserve {
+            return Err(ContractError::InsufficientRewardReserve {});
+        }
+        REWARD_RESERVES.save(storage, token, &reserve.checked_sub(amount)?)?;
+        Ok(())
+    }
+
+    /// Settles `address`'s rewards against its stored balances, after
+    /// accruing `pool` and taking expired boosts out of it. Must run before
+    /// those balances or the pool totals change in this transaction.
+    fn checkpoint_rewards(
+        storage: &mut dyn Storage,
+        pool: &mut PoolInfo,
+        address: &Addr,
+        user: &UserInfo,
+        now: u64,
+    ) -> Result<(), ContractError> {
+        let config = CONFIG.load(storage)?;
+        accrue_pool(&config, pool, now)?;
+        let mut state = REWARD_STATE.may_load(storage)?;
+        expire_boosts(storage, pool, state.as_mut(), now)?;
+        let mut state = match state {
+            Some(state) => state,
+            None => return Ok(()),
+        };
+        update_reward_indices(&mut state, pool, now)?;
+        REWARD_STATE.save(storage, &state)?;
+
+        let mut user = user.clone();
+        accrue_user(&mut pool.clone(), &mut user, now)?;
+        let mut rewards = USER_REWARDS.may_load(storage, address)?.unwrap_or_default();
+        let settled = settle_rewards(storage, &state, &mut rewards, address, &user, now, &[])?;
+        for (token, amount) in settled {
+            SETTLED_REWARDS.update(storage, (address, &token), |settled| -> StdResult<_> {
+                Ok(settled.unwrap_or_default().checked_add(amount)?)
+            })?;
+        }
+        USER_REWARDS.save(storage, address, &rewards)?;
+        Ok(())
+    }
+
+    /// Brings `rewards` up to `state`, weighting by an accrued `user`.
+    /// Returns what was earned in epochs closed since the last checkpoint,
+    /// by reward token.
+    pub fn settle_rewards(
+        storage: &dyn Storage,
+        state: &RewardState,
+        rewards: &mut UserRewards,
+        address: &Addr,
+        user: &UserInfo,
+        now: u64,
+        swept: &[(u64, LockExpiry)],
+    ) -> Result<Vec<(Addr, Uint128)>, ContractError> {
+        let mut settled = vec![];
+        while rewards.epoch < state.epoch {
+            let closed = REWARD_EPOCHS.load(storage, rewards.epoch)?;
+            let closed_state = RewardState {
+                reward_token: closed.reward_token,
+                supply_index: closed.supply_index,
+                borrow_index: closed.borrow_index,
+                epoch: rewards.epoch,
+                ..state.clone()
+            };
+            let expired = expired_boosts(storage, address, now, swept, rewards.epoch)?;
+            update_user_rewards(&closed_state, rewards, user, &expired)?;
+            if !rewards.pending.is_zero() {
+                settled.push((closed_state.reward_token, rewards.pending));
+            }
+            *rewards = UserRewards { epoch: rewards.epoch + 1, ..UserRewards::default() };
+        }
+        let expired = expired_boosts(storage, address, now, swept, state.epoch)?;
+        update_user_rewards(state, rewards, user, &expired)?;
+        Ok(settled)
+    }
+
+    /// Takes the boost of locks that expired by `now` out of `pool` and saves
+    /// the expiries. See `due_expiries`.
+    fn expire_boosts(
+        storage: &mut dyn Storage,
+        pool: &mut PoolInfo,
+        state: Option<&mut RewardState>,
+        now: u64,
+    ) -> Result<(), ContractError> {
+        for (unlock_time, expiry) in due_expiries(storage, pool, state, now)? {
+            LOCK_EXPIRIES.save(storage, unlock_time, &expiry)?;
+        }
+        Ok(())
+    }
+
+    /// Expiries up to `now` whose boost is still in `pool.total_boost`, with
+    /// that boost taken out. The reward indices are advanced to each unlock
+    /// time on the way, so the boost earns up to its unlock time and no
+    /// further. Returns the updated expiries without saving them.
+    pub fn due_expiries(
+        storage: &dyn Storage,
+        pool: &mut PoolInfo,
+        mut state: Option<&mut RewardState>,
+        now: u64,
+    ) -> Result<Vec<(u64, LockExpiry)>, ContractError> {
+        let due = LOCK_EXPIRIES
+            .range(storage, None, Some(Bound::inclusive(now)), Order::Ascending)
+            .filter(|item| !matches!(item, Ok((_, expiry)) if expiry.supply_index.is_some()))
+            .collect::<StdResult<Vec<_>>>()?;
+
+        let mut expired = Vec::with_capacity(due.len());
+        for (unlock_time, mut expiry) in due {
+            let (supply_index, epoch) = match state.as_deref_mut() {
+                Some(state) => {
+                    update_reward_indices(state, pool, unlock_time.max(state.last_update))?;
+                    (state.supply_index, state.epoch)
+                }
+                None => (Decimal::zero(), 0),
+            };
+            pool.total_boost = pool.total_boost.checked_sub(expiry.boost)?;
+            expiry.supply_index = Some(supply_index);
+            expiry.epoch = epoch;
+            expired.push((unlock_time, expiry));
+        }
+        Ok(expired)
+    }
+
+    /// The boost of each of `address`'s locks that expired by the end of
+    /// reward `epoch`, with the supply index it earns up to in that epoch.
+    /// Expiries are read from `swept` or else from storage.
+    pub fn expired_boosts(
+        storage: &dyn Storage,
+        address: &Addr,
+        now: u64,
+        swept: &[(u64, LockExpiry)],
+        epoch: u64,
+    ) -> Result<Vec<(Uint128, Decimal)>, ContractError> {
+        let mut expired = vec![];
+        for (_, lock) in expired_locks(storage, address, now)? {
+            let expiry = match swept.iter().find(|(time, _)| *time == lock.unlock_time) {
+                Some((_, expiry)) => expiry.clone(),
+                None => LOCK_EXPIRIES.load(storage, lock.unlock_time)?,
+            };
+            // A boost that expired in an earlier epoch earns nothing in this
+            // one, and one that expires later earns all of it.
+            if expiry.epoch < epoch {
+                expired.push((lock.boost()?, Decimal::zero()));
+            } else if expiry.epoch == epoch {
+                expired.push((lock.boost()?, expiry.supply_index.unwrap_or_default()));
+            }
+        }
+        Ok(expired)
+    }
+
+    /// Drops `address`'s expired locks from the position.
+    fn release_expired_locks(
+        storage: &mut dyn Storage,
+        address: &Addr,
+        user: &mut UserInfo,
+        now: u64,
+    ) -> Result<(), ContractError> {
+        let expired = expired_locks(storage, address, now)?;
+        release_locks(user, &expired)?;
+        for (id, lock) in expired {
+            remove_lock(storage, address, id, &lock)?;
+        }
+        Ok(())
+    }
+
+    pub fn expired_locks(storage: &dyn Storage, address: &Addr, now: u64) -> StdResult<Vec<(u64, Lock)>> {
+        LOCKS
+            .prefix(address)
+            .range(storage, None, None, Order::Ascending)
+            .filter(|item| match item {
+                Ok((_, lock)) => lock.unlock_time <= now,
+                Err(_) => true,
+            })
+            .collect()
+    }
+
+    /// Removes the locked amount and boost of expired `locks` from the
+    /// position. Their boost already left `pool.total_boost` at expiry.
+    pub fn release_locks(user: &mut UserInfo, locks: &[(u64, Lock)]) -> Result<(), ContractError> {
+        for (_, lock) in locks {
+            user.locked_amount = user.locked_amount.saturating_sub(lock.amount);
+            user.boosted_amount = user.boosted_amount.saturating_sub(lock.boost()?);
+        }
+        Ok(())
+    }
+
+    /// Deletes a lock and its share of the expiry at its unlock time.
+    fn remove_lock(
+        storage: &mut dyn Storage,
+        address: &Addr,
+        id: u64,
+        lock: &Lock,
+    ) -> Result<(), ContractError> {
+        LOCKS.remove(storage, (address, id));
+        let mut expiry = LOCK_EXPIRIES.load(storage, lock.unlock_time)?;
+        expiry.locks -= 1;
+        if expiry.locks == 0 {
+            LOCK_EXPIRIES.remove(storage, lock.unlock_time);
+            return Ok(());
+        }
+        if expiry.supply_index.is_none() {
+            expiry.boost = expiry.boost.checked_sub(lock.boost()?)?;
+        }
+        LOCK_EXPIRIES.save(storage, lock.unlock_time, &expiry)?;
+        Ok(())
+    }
+
+    /// Drops every lock of a position whose collateral is seized, along with
+    /// its boost.
+    fn forfeit_locks(
+        storage: &mut dyn Storage,
+        pool: &mut PoolInfo,
+        address: &Addr,
+        user: &mut UserInfo,
+    ) -> Result<(), ContractError> {
+        remove_locks(storage, address)?;
+        clear_boost(pool, user);
+        Ok(())
+    }
+
+    fn remove_locks(storage: &mut dyn Storage, address: &Addr) -> Result<(), ContractError> {
+        let locks = LOCKS
+            .prefix(address)
+            .range(storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        for (id, lock) in locks {
+            remove_lock(storage, address, id, &lock)?;
+        }
+        Ok(())
+    }
+
+    fn clear_boost(pool: &mut PoolInfo, user: &mut UserInfo) {
+        pool.total_boost = pool.total_boost.saturating_sub(user.boosted_amount);
+        user.boosted_amount = Uint128::zero();
+        user.locked_amount = Uint128::zero();
+    }
+
+    /// One position event for each side of a transfer, both carrying the
+    /// `sender` and `recipient`.
+    fn transfer_events(
+        action: &str,
+        asset: &Addr,
+        amount: Uint128,
+        (sender, from): (&Addr, &UserInfo),
+        (recipient, to): (&Addr, &UserInfo),
+        pool: &PoolInfo,
+        now: u64,
+    ) -> Vec<Event> {
+        [(sender, from), (recipient, to)]
+            .iter()
+            .map(|&(user, position)| {
+                position_event(action, user, asset, amount, position, pool, now)
+                    .add_attribute("sender", sender.to_string())
+                    .add_attribute("recipient", recipient.to_string())
+            })
+            .collect()
+    }
+
+    pub fn load_user(storage: &dyn Storage, address: &Addr) -> Result<UserInfo, ContractError> {
+        USERS.may_load(storage, address)?.ok_or(ContractError::NoPosition {})
+    }
+
+    fn is_healthy(config: &Config, user: &UserInfo) -> Result<bool, ContractError> {
+        Ok(total_debt(user)? <= borrow_limit(config, user)?)
+    }
+
+    fn receipt_token(config: &Config) -> Result<Addr, ContractError> {
+        config.receipt_token.clone().ok_or(ContractError::ReceiptTokenNotSet {})
+    }
+
+    fn burn_receipt_msg(config: &Config, owner: &Addr, amount: Uint128) -> Result<WasmMsg, ContractError> {
+        Ok(WasmMsg::Execute {
+            contract_addr: receipt_token(config)?.to_string(),
+            msg: to_json_binary(&Cw20ExecuteMsg::BurnFrom {
+                owner: owner.to_string(),
+                amount,
+            })?,
+            funds: vec![],
+        })
+    }
+
+    fn transfer_msg(token: &Addr, recipient: &Addr, amount: Uint128) -> StdResult<WasmMsg> {
+        Ok(WasmMsg::Execute {
+            contract_addr: token.to_string(),
+            msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: recipient.to_string(),
+                amount,
+            })?,
+            funds: vec![],
+        })
+    }
+
+    /// Fails unless the pool holds `amount` of OM beyond the payouts it is
+    /// holding for `ClaimPayout` and any OM reward reserve.
+    pub fn check_liquidity(
+        deps: Deps,
+        contract: &Addr,
+        config: &Config,
+        amount: Uint128,
+    ) -> Result<(), ContractError> {
+        let balance: BalanceResponse = deps.querier.query_wasm_smart(
+            &config.om_token,
+            &Cw20QueryMsg::Balance { address: contract.to_string() },
+        )?;
+        let held = UNCLAIMED_TOTAL
+            .may_load(deps.storage)?
+            .unwrap_or_default()
+            .checked_add(REWARD_RESERVES.may_load(deps.storage, &config.om_token)?.unwrap_or_default())?;
+        if amount > balance.balance.saturating_sub(held) {
+            return Err(ContractError::InsufficientLiquidity {});
+        }
+        Ok(())
+    }
+
+    /// Why the OM token would reject a transfer to `recipient`, if it is
+    /// paused or has blocked them. Tokens without these queries never do.
+    fn payout_hold_reason(deps: Deps, config: &Config, recipient: &Addr) -> Option<&'static str> {
+        let compliance: StdResult<ComplianceResponse> = deps
+            .querier
+            .query_wasm_smart(&config.om_token, &TokenQueryMsg::Compliance {});
+        if compliance.is_ok_and(|compliance| compliance.paused) {
+            return Some("paused");
+        }
+        let blocked: StdResult<BlockedResponse> = deps.querier.query_wasm_smart(
+            &config.om_token,
+            &TokenQueryMsg::Blocked { address: recipient.to_string() },
+        );
+        blocked.is_ok_and(|blocked| blocked.blocked).then_some("blocked")
+    }
+
+    /// Transfers borrowed OM to `recipient`. While the token is paused or has
+    /// blocked `recipient` the OM is held for `ClaimPayout` instead, so the
+    /// borrow still goes through; any other failed transfer reverts it.
+    fn payout(
+        deps: DepsMut,
+        config: &Config,
+        recipient: &Addr,
+        amount: Uint128,
+        now: u64,
+    ) -> Result<Response, ContractError> {
+        let reason = match payout_hold_reason(deps.as_ref(), config, recipient) {
+            Some(reason) => reason,
+            None => {
+                let msg = transfer_msg(&config.om_token, recipient, amount)?;
+                return Ok(Response::new().add_message(msg));
+            }
+        };
+
+        UNCLAIMED_PAYOUTS.update(deps.storage, recipient, |unclaimed| -> StdResult<_> {
+            Ok(unclaimed.unwrap_or_default().checked_add(amount)?)
+        })?;
+        let held = UNCLAIMED_TOTAL.may_load(deps.storage)?.unwrap_or_default();
+        UNCLAIMED_TOTAL.save(deps.storage, &held.checked_add(amount)?)?;
+
+        let event = admin_event("payout_deferred", now)
+            .add_attribute("user", recipient)
+            .add_attribute("amount", amount.to_string())
+            .add_attribute("reason", reason);
+        Ok(Response::new().add_event(event))
+    }
+
+    pub fn claim_payout(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+        let config = CONFIG.load(deps.storage)?;
+        let amount = UNCLAIMED_PAYOUTS
+            .may_load(deps.storage, &info.sender)?
+            .ok_or(ContractError::NoPayout {})?;
+        if payout_hold_reason(deps.as_ref(), &config, &info.sender).is_some() {
+            return Err(ContractError::PayoutHeld {});
+        }
+        UNCLAIMED_PAYOUTS.remove(deps.storage, &info.sender);
+        let held = UNCLAIMED_TOTAL.load(deps.storage)?;
+        UNCLAIMED_TOTAL.save(deps.storage, &held.checked_sub(amount)?)?;
+
+        let event = admin_event("claim_payout", env.block.time.seconds())
+            .add_attribute("user", &info.sender)
+            .add_attribute("amount", amount.to_string());
+        Ok(Response::new()
+            .add_message(transfer_msg(&config.om_token, &info.sender, amount)?)
+            .add_event(event)
+            .add_attribute("action", "claim_payout")
+            .add_attribute("amount", amount.to_string()))
     }
 }
 
 mod query {
     use super::*;
 
-    pub fn config(deps: Deps) -> StdResult<Config> {
-        CONFIG.load(deps.storage)
+    pub fn config(deps: Deps) -> StdResult<ConfigResponse> {
+        let config = CONFIG.load(deps.storage)?;
+        Ok(ConfigResponse {
+            owner: config.owner.to_string(),
+            usd_token: config.usd_token.to_string(),
+            om_token: config.om_token.to_string(),
+            collateral_ratio: config.collateral_ratio,
+            interest_rate: config.interest_rate,
+            penalty_rate: config.penalty_rate,
+            rate_slope: config.rate_slope,
+            rebalance_utilization: config.rebalance_utilization,
+            unbonding_period: config.unbonding_period,
+            guardian: config.guardian.to_string(),
+            timelock_delay: config.timelock_delay,
+            auction_start_price: config.auction_start_price,
+            auction_step: config.auction_step,
+            liquidation_bonus: config.liquidation_bonus,
+            close_factor: config.close_factor,
+            receipt_token: config.receipt_token.map(|addr| addr.to_string()),
+        })
+    }
+
+    pub fn user_info(deps: Deps, env: Env, address: String) -> Result<UserInfoResponse, ContractError> {
+        let config = CONFIG.load(deps.storage)?;
+        let mut pool = POOL.load(deps.storage)?;
+        let address = deps.api.addr_validate(&address)?;
+        let mut user = USERS.may_load(deps.storage, &address)?.unwrap_or_default();
+
+        let now = env.block.time.seconds();
+        accrue_pool(&config, &mut pool, now)?;
+        accrue_user(&mut pool, &mut user, now)?;
+
+        user_info_response(&config, &user)
+    }
+
+    fn user_info_response(config: &Config, user: &UserInfo) -> Result<UserInfoResponse, ContractError> {
+        let total_debt = total_debt(user)?;
+        let borrow_limit = borrow_limit(config, user)?;
+        Ok(UserInfoResponse {
+            staked_amount: user.staked_amount,
+            locked_amount: user.locked_amount,
+            boosted_amount: user.boosted_amount,
+            borrowed_amount: user.borrowed_amount,
+            fixed_borrowed: user.fixed_borrowed,
+            rate_mode: user.rate_mode,
+            stable_rate: user.stable_rate,
+            total_debt,
+            borrow_limit,
+            available_to_borrow: borrow_limit.saturating_sub(total_debt),
+        })
+    }
+
+    pub fn simulate_borrow(
+        deps: Deps,
+        env: Env,
+        address: String,
+        amount: Uint128,
+        rate_mode: Option<RateMode>,
+    ) -> Result<SimulationResponse, ContractError> {
+        let contract = env.contract.address.clone();
+        simulate(deps, env, address, |config, pool, user, _, now| {
+            execute::check_liquidity(deps, &contract, config, amount)?;
+            execute::apply_borrow(config, pool, user, amount, rate_mode, now).map(|_| ())
+        })
+    }
+
+    pub fn simulate_unstake(
+        deps: Deps,
+        env: Env,
+        address: String,
+        amount: Uint128,
+    ) -> Result<SimulationResponse, ContractError> {
+        simulate(deps, env, address, |config, pool, user, address, now| {
+            let expired = execute::expired_locks(deps.storage, address, now)?;
+            execute::apply_unstake(config, pool, user, &expired, amount, now)
+        })
+    }
+
+    pub fn simulate_repay(
+        deps: Deps,
+        env: Env,
+        address: String,
+        amount: Uint128,
+    ) -> Result<SimulationResponse, ContractError> {
+        simulate(deps, env, address, |config, pool, user, _, now| {
+            execute::apply_repay(config, pool, user, amount, now)
+        })
+    }
+
+    pub fn simulate_liquidation(
+        deps: Deps,
+        env: Env,
+        borrower: String,
+        amount: Uint128,
+    ) -> Result<SimulationResponse, ContractError> {
+        simulate(deps, env, borrower, |config, pool, user, address, now| {
+            let expired = execute::expired_locks(deps.storage, address, now)?;
+            execute::release_locks(user, &expired)?;
+            execute::apply_liquidation(config, pool, user, amount, now).map(|_| ())
+        })
+    }
+
+    /// Runs `action` on in-memory copies of the pool and `address`'s
+    /// position, reporting the error instead of failing the query.
+    fn simulate<F>(
+        deps: Deps,
+        env: Env,
+        address: String,
+        action: F,
+    ) -> Result<SimulationResponse, ContractError>
+    where
+        F: FnOnce(&Config, &mut PoolInfo, &mut UserInfo, &Addr, u64) -> Result<(), ContractError>,
+    {
+        let address = deps.api.addr_validate(&address)?;
+        let config = CONFIG.load(deps.storage)?;
+        let mut pool = POOL.load(deps.storage)?;
+        let now = env.block.time.seconds();
+        accrue_pool(&config, &mut pool, now)?;
+        let variable_rate_before = variable_rate(&config, &pool)?;
+
+        let (user, pool, error) = match execute::load_user(deps.storage, &address) {
+            Ok(mut user) => {
+                accrue_user(&mut pool, &mut user, now)?;
+                let (mut after, mut pool_after) = (user.clone(), pool.clone());
+                match action(&config, &mut pool_after, &mut after, &address, now) {
+                    Ok(()) => (after, pool_after, None),
+                    Err(err) => (user, pool, Some(err.to_string())),
+                }
+            }
+            Err(err) => (UserInfo::default(), pool, Some(err.to_string())),
+        };
+
+        let position = user_info_response(&config, &user)?;
+        let health_factor = if position.total_debt.is_zero() {
+            None
+        } else {
+            Some(ratio(position.borrow_limit, position.total_debt)?)
+        };
+        Ok(SimulationResponse {
+            error,
+            position,
+            health_factor,
+            variable_rate_before,
+            variable_rate_after: variable_rate(&config, &pool)?,
+        })
+    }
+
+    pub fn pool_info(deps: Deps, env: Env) -> Result<PoolInfoResponse, ContractError> {
+        let config = CONFIG.load(deps.storage)?;
+        let mut pool = POOL.load(deps.storage)?;
+        accrue_pool(&config, &mut pool, env.block.time.seconds())?;
+
+        Ok(PoolInfoResponse {
+            total_staked: pool.total_staked,
+            total_borrowed: pool.total_borrowed,
+            total_boost: pool.total_boost,
+            variable_borrowed: pool.variable_borrowed,
+            stable_borrowed: pool.stable_borrowed,
+            borrow_index: pool.borrow_index,
+            utilization: utilization(&pool),
+            available_liquidity: pool.total_staked.saturating_sub(pool.total_borrowed),
+        })
+    }
+
+    pub fn rates(deps: Deps) -> Result<RatesResponse, ContractError> {
+        let config = CONFIG.load(deps.storage)?;
+        let pool = POOL.load(deps.storage)?;
+        Ok(RatesResponse {
+            utilization: utilization(&pool),
+            variable_rate: variable_rate(&config, &pool)?,
+            average_stable_rate: average_stable_rate(&pool)?,
+        })
+    }
+
+    pub fn pending_rewards(
+        deps: Deps,
+        env: Env,
+        address: String,
+    ) -> Result<PendingRewardsResponse, ContractError> {
+        let address = deps.api.addr_validate(&address)?;
+        let mut settled = SETTLED_REWARDS
+            .prefix(&address)
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        let mut state = match REWARD_STATE.may_load(deps.storage)? {
+            Some(state) => state,
+            None => {
+                return Ok(PendingRewardsResponse {
+                    reward_token: None,
+                    pending: Uint128::zero(),
+                    settled: vec![],
+                })
+            }
+        };
+        let config = CONFIG.load(deps.storage)?;
+        let mut pool = POOL.load(deps.storage)?;
+        let mut user = USERS.may_load(deps.storage, &address)?.unwrap_or_default();
+        let mut rewards = USER_REWARDS.may_load(deps.storage, &address)?.unwrap_or_default();
+
+        let now = env.block.time.seconds();
+        accrue_pool(&config, &mut pool, now)?;
+        let swept = execute::due_expiries(deps.storage, &mut pool, Some(&mut state), now)?;
+        update_reward_indices(&mut state, &pool, now)?;
+        accrue_user(&mut pool, &mut user, now)?;
+        let closed =
+            execute::settle_rewards(deps.storage, &state, &mut rewards, &address, &user, now, &swept)?;
+        for (token, amount) in closed {
+            match settled.iter_mut().find(|(settled, _)| *settled == token) {
+                Some((_, total)) => *total = total.checked_add(amount)?,
+                None => settled.push((token, amount)),
+            }
+        }
+
+        Ok(PendingRewardsResponse {
+            reward_token: Some(state.reward_token.into_string()),
+            pending: rewards.pending,
+            settled: settled
+                .into_iter()
+                .map(|(token, amount)| Cw20Coin { address: token.into_string(), amount })
+                .collect(),
+        })
+    }
+
+    pub fn debt_balance(deps: Deps, env: Env, address: String) -> Result<BalanceResponse, ContractError> {
+        let address = deps.api.addr_validate(&address)?;
+        let config = CONFIG.load(deps.storage)?;
+        let mut pool = POOL.load(deps.storage)?;
+        let mut user = USERS.may_load(deps.storage, &address)?.unwrap_or_default();
+
+        let now = env.block.time.seconds();
+        accrue_pool(&config, &mut pool, now)?;
+        accrue_user(&mut pool, &mut user, now)?;
+
+        Ok(BalanceResponse {
+            balance: total_debt(&user)?,
+        })
+    }
+
+    pub fn debt_token_info(deps: Deps, env: Env) -> Result<TokenInfoResponse, ContractError> {
+        let config = CONFIG.load(deps.storage)?;
+        let mut pool = POOL.load(deps.storage)?;
+        accrue_pool(&config, &mut pool, env.block.time.seconds())?;
+
+        Ok(TokenInfoResponse {
+            name: DEBT_NAME.to_string(),
+            symbol: DEBT_SYMBOL.to_string(),
+            decimals: DEBT_DECIMALS,
+            total_supply: pool.total_borrowed,
+        })
+    }
+
+    pub fn debt_allowance(deps: Deps, owner: String, spender: String) -> StdResult<AllowanceResponse> {
+        let owner = deps.api.addr_validate(&owner)?;
+        let spender = deps.api.addr_validate(&spender)?;
+        let allowance = DEBT_ALLOWANCES
+            .may_load(deps.storage, (&owner, &spender))?
+            .unwrap_or_default();
+
+        Ok(AllowanceResponse {
+            allowance,
+            expires: Expiration::Never {},
+        })
+    }
+
+    pub fn user_locks(deps: Deps, address: String) -> StdResult<Vec<LockResponse>> {
+        let address = deps.api.addr_validate(&address)?;
+        LOCKS
+            .prefix(&address)
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| {
+                item.map(|(_, lock)| LockResponse {
+                    id: lock.id,
+                    amount: lock.amount,
+                    multiplier: lock.multiplier,
+                    unlock_time: lock.unlock_time,
+                })
+            })
+            .collect()
+    }
+
+    pub fn unbonding(deps: Deps, address: String) -> StdResult<Vec<UnbondingResponse>> {
+        let address = deps.api.addr_validate(&address)?;
+        UNBONDING
+            .prefix(&address)
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| {
+                item.map(|(_, unbonding)| UnbondingResponse {
+                    id: unbonding.id,
+                    amount: unbonding.amount,
+                    release_time: unbonding.release_time,
+                })
+            })
+            .collect()
+    }
+
+    pub fn pending_changes(deps: Deps) -> StdResult<Vec<PendingChangeResponse>> {
+        PENDING_CHANGES
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| {
+                item.map(|(_, pending)| PendingChangeResponse {
+                    id: pending.id,
+                    change: pending.change,
+                    eta: pending.eta,
+                })
+            })
+            .collect()
+    }
+
+    pub fn auction(deps: Deps, env: Env, auction_id: u64) -> Result<AuctionResponse, ContractError> {
+        let auction = AUCTIONS.load(deps.storage, auction_id)?;
+        auction_response(auction, env.block.time.seconds())
+    }
+
+    pub fn auctions(deps: Deps, env: Env) -> Result<Vec<AuctionResponse>, ContractError> {
+        let now = env.block.time.seconds();
+        AUCTIONS
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| auction_response(item?.1, now))
+            .collect()
+    }
+
+    fn auction_response(auction: Auction, now: u64) -> Result<AuctionResponse, ContractError> {
+        Ok(AuctionResponse {
+            price: auction.price(now)?,
+            payout: auction.payout(now)?,
+            id: auction.id,
+            borrower: auction.borrower.to_string(),
+            debt: auction.debt,
+            collateral: auction.collateral,
+            start_time: auction.start_time,
+            start_price: auction.start_price,
+            step: auction.step,
+        })
+    }
+
+    pub fn loan(deps: Deps, loan_id: u64) -> StdResult<LoanResponse> {
+        Ok(loan_response(LOANS.load(deps.storage, loan_id)?))
+    }
+
+    fn loan_response(loan: Loan) -> LoanResponse {
+        LoanResponse {
+            id: loan.id,
+            borrower: loan.borrower.to_string(),
+            principal: loan.principal,
+            rate: loan.rate,
+            start_time: loan.start_time,
+            maturity: loan.maturity,
+        }
     }
 
-    pub fn user_info(deps: Deps, address: Addr) -> StdResult<UserInfo> {
-        USERS.may_load(deps.storage, &address)?.ok_or_else(|| StdError::not_found("UserInfo"))
+    pub fn unclaimed_payout(deps: Deps, address: String) -> StdResult<BalanceResponse> {
+        let address = deps.api.addr_validate(&address)?;
+        let balance = UNCLAIMED_PAYOUTS.may_load(deps.storage, &address)?.unwrap_or_default();
+        Ok(BalanceResponse { balance })
     }
 
-    pub fn pool_info(deps: Deps) -> StdResult<PoolInfo> {
-        POOL.load(deps.storage)
+    pub fn user_loans(deps: Deps, address: String) -> StdResult<Vec<LoanResponse>> {
+        let address = deps.api.addr_validate(&address)?;
+        USER_LOANS
+            .prefix(&address)
+            .keys(deps.storage, None, None, Order::Ascending)
+            .map(|id| Ok(loan_response(LOANS.load(deps.storage, id?)?)))
+            .collect()
     }
 }
\ No newline at end of file
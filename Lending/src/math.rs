@@ -0,0 +1,123 @@
+use std::convert::TryFrom;
+
+use cosmwasm_std::{Decimal, Decimal256, OverflowError, OverflowOperation, Uint128, Uint256};
+
+use crate::error::ContractError;
+
+/// Which way a result that does not divide evenly is rounded. Amounts owed
+/// to the pool round up and amounts paid out of it round down, so rounding
+/// never works against the protocol.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rounding {
+    Down,
+    Up,
+}
+
+/// `value * numerator / denominator`, with the product taken in 256 bits.
+pub fn mul_ratio(
+    value: Uint128,
+    numerator: impl Into<Uint256>,
+    denominator: impl Into<Uint256>,
+    rounding: Rounding,
+) -> Result<Uint128, ContractError> {
+    let numerator = numerator.into();
+    let denominator = denominator.into();
+    let product = Uint256::from(value).checked_mul(numerator)?;
+    let mut result = product.checked_div(denominator)?;
+    if rounding == Rounding::Up && !product.checked_rem(denominator)?.is_zero() {
+        result = result.checked_add(Uint256::one())?;
+    }
+    Uint128::try_from(result)
+        .map_err(|_| OverflowError::new(OverflowOperation::Mul, value, numerator).into())
+}
+
+/// `value * factor`.
+pub fn mul_decimal(
+    value: Uint128,
+    factor: Decimal,
+    rounding: Rounding,
+) -> Result<Uint128, ContractError> {
+    mul_ratio(value, factor.atomics(), Decimal::one().atomics(), rounding)
+}
+
+/// Product of two fixed-point numbers, computed as `Decimal256` and checked
+/// to fit back into a `Decimal`. Rounds down at 18 decimal places.
+pub fn mul_decimals(a: Decimal, b: Decimal) -> Result<Decimal, ContractError> {
+    let product = Decimal256::from(a).checked_mul(Decimal256::from(b))?;
+    Decimal::try_from(product).map_err(|_| OverflowError::new(OverflowOperation::Mul, a, b).into())
+}
+
+/// `numerator / denominator` as a fixed-point number, rounded down.
+pub fn ratio(numerator: Uint128, denominator: Uint128) -> Result<Decimal, ContractError> {
+    let atomics = mul_ratio(
+        numerator,
+        Decimal::one().atomics(),
+        denominator,
+        Rounding::Down,
+    )?;
+    Ok(Decimal::new(atomics))
+}
+
+/// Takes one position's `amount` out of a pool total. Positions accrue
+/// interest rounded up and the pool totals round down, so a total can fall
+/// a few units short of the positions it sums; it floors at zero instead of
+/// failing the position's repayment.
+pub fn sub_pool_total(total: Uint128, amount: Uint128) -> Uint128 {
+    total.saturating_sub(amount)
+}
+
+/// `now + seconds` for deadlines derived from user or governance input.
+pub fn add_seconds(now: u64, seconds: u64) -> Result<u64, ContractError> {
+    now.checked_add(seconds)
+        .ok_or_else(|| OverflowError::new(OverflowOperation::Add, now, seconds).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rounding_direction() {
+        let value = Uint128::new(10);
+        assert_eq!(
+            mul_ratio(value, 1u128, 3u128, Rounding::Down).unwrap(),
+            Uint128::new(3)
+        );
+        assert_eq!(
+            mul_ratio(value, 1u128, 3u128, Rounding::Up).unwrap(),
+            Uint128::new(4)
+        );
+        // Exact results are not bumped.
+        assert_eq!(mul_ratio(value, 3u128, 3u128, Rounding::Up).unwrap(), value);
+        assert_eq!(
+            mul_decimal(value, Decimal::percent(15), Rounding::Up).unwrap(),
+            Uint128::new(2)
+        );
+    }
+
+    #[test]
+    fn pool_totals_floor_at_zero() {
+        let total = Uint128::new(10);
+        assert_eq!(sub_pool_total(total, Uint128::new(4)), Uint128::new(6));
+        assert_eq!(sub_pool_total(total, Uint128::new(11)), Uint128::zero());
+    }
+
+    #[test]
+    fn wide_intermediates() {
+        // The product overflows 128 bits but the result does not.
+        let max = Uint128::MAX;
+        assert_eq!(mul_ratio(max, max, max, Rounding::Down).unwrap(), max);
+    }
+
+    #[test]
+    fn overflow_is_an_error() {
+        let err = mul_ratio(Uint128::MAX, 2u128, 1u128, Rounding::Down).unwrap_err();
+        assert!(matches!(err, ContractError::Overflow(_)));
+        let err = mul_ratio(Uint128::one(), 1u128, 0u128, Rounding::Down).unwrap_err();
+        assert!(matches!(err, ContractError::DivideByZero(_)));
+        let err = mul_decimals(Decimal::MAX, Decimal::percent(200)).unwrap_err();
+        assert!(matches!(err, ContractError::Overflow(_)));
+        let err = add_seconds(u64::MAX, 1).unwrap_err();
+        assert!(matches!(err, ContractError::Overflow(_)));
+    }
+}
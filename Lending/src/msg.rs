@@ -1,7 +1,9 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use cosmwasm_std::{Addr, Uint128};
-use cw20::Cw20ReceiveMsg;
+use std::fmt;
+use cosmwasm_std::{Decimal, Uint128};
+use cw20::{Cw20Coin, Cw20ReceiveMsg};
+use token::msg::Permit;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InstantiateMsg {
@@ -9,22 +11,376 @@ pub struct InstantiateMsg {
     pub om_token: String,
     pub collateral_ratio: Uint128,
     pub interest_rate: Uint128,
+    pub penalty_rate: Uint128,
+    pub rate_slope: Uint128,
+    pub rebalance_utilization: Uint128,
+    pub unbonding_period: u64,
+    pub guardian: String,
+    pub timelock_delay: u64,
+    pub auction_start_price: Decimal,
+    pub auction_step: Decimal,
+    pub liquidation_bonus: Uint128,
+    pub close_factor: Uint128,
+    /// Code id of the `token` contract used for supplier receipts.
+    pub receipt_code_id: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RateMode {
+    #[default]
+    Variable,
+    Stable,
+}
+
+impl fmt::Display for RateMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RateMode::Variable => write!(f, "variable"),
+            RateMode::Stable => write!(f, "stable"),
+        }
+    }
+}
+
+/// Risk parameters changed through the timelock. Fields left as `None` keep
+/// their current value.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct ConfigChange {
+    pub collateral_ratio: Option<Uint128>,
+    pub interest_rate: Option<Uint128>,
+    pub penalty_rate: Option<Uint128>,
+    pub rate_slope: Option<Uint128>,
+    pub rebalance_utilization: Option<Uint128>,
+    pub unbonding_period: Option<u64>,
+    pub auction_start_price: Option<Decimal>,
+    pub auction_step: Option<Decimal>,
+    pub liquidation_bonus: Option<Uint128>,
+    pub close_factor: Option<Uint128>,
+    pub guardian: Option<String>,
+    pub timelock_delay: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
-    Stake {},
+    /// Stakes `permit.amount` of the USD token for `permit.owner`, using an
+    /// allowance the owner signed for this contract. Anyone may submit it.
+    StakeWithPermit { permit: Permit },
     Unstake { amount: Uint128 },
-    Borrow { amount: Uint128 },
-    Repay {},
+    ClaimUnbonded {},
+    Borrow { amount: Uint128, rate_mode: Option<RateMode> },
+    BorrowFixed { amount: Uint128, term_seconds: u64 },
+    /// Seizes the collateral of an unhealthy position and auctions it for
+    /// the open-ended debt. Collateral backing fixed-term loans is left in
+    /// place for `Cw20HookMsg::LiquidateLoan`.
+    StartAuction { borrower: String },
+    RebalanceStableRate { address: String },
+    /// Hook from the receipt token, see `token::msg::ControllerHookMsg`.
+    ReceiptTransfer { sender: String, recipient: String, amount: Uint128 },
+    ApproveDelegation { delegatee: String, amount: Uint128 },
+    TransferDebt { recipient: String, amount: Uint128 },
+    /// Sets the reward token and per-second emissions for each side. Rewards
+    /// earned before a token change stay claimable in the old token.
+    /// Emissions are funded through `Cw20HookMsg::FundRewards`.
+    UpdateRewards { reward_token: String, supply_rate: Uint128, borrow_rate: Uint128 },
+    ClaimRewards {},
+    /// Owner only. Queues `change` to become executable after
+    /// `Config.timelock_delay` seconds.
+    QueueConfigChange { change: Box<ConfigChange> },
+    ExecuteConfigChange { id: u64 },
+    /// Owner or guardian.
+    CancelConfigChange { id: u64 },
+    /// Retries the transfer of borrowed OM the token rejected at borrow time.
+    ClaimPayout {},
     Receive(Cw20ReceiveMsg),
 }
 
+/// Hooks accepted through a CW20 `Send`, in place of `ExecuteMsg`. `Stake`
+/// and `StakeLocked` take the USD token; every other hook takes the OM debt
+/// token. Optional addresses default to the CW20 sender.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Cw20HookMsg {
+    /// Supplies the sent USD, crediting `on_behalf_of`.
+    Stake { on_behalf_of: Option<String> },
+    /// Supplies the sent USD for `on_behalf_of`, locked for `duration`
+    /// seconds: 30, 90 or 180 days.
+    StakeLocked { duration: u64, on_behalf_of: Option<String> },
+    /// Repays open-ended debt of `on_behalf_of`.
+    Repay { on_behalf_of: Option<String> },
+    /// Repays up to the sent amount of an unhealthy borrower's open-ended
+    /// debt, at most `Config.close_factor` of it, and pays the amount repaid
+    /// plus `Config.liquidation_bonus` of their collateral to `recipient`.
+    Liquidate { borrower: String, recipient: Option<String> },
+    /// Repays fixed-term loan `loan_id` in full, with any penalty once past
+    /// due. The excess is refunded.
+    RepayLoan { loan_id: u64 },
+    /// Repays past-due loan `loan_id` in full and pays the same amount of the
+    /// borrower's collateral to `recipient`. The excess is refunded.
+    LiquidateLoan { loan_id: u64, recipient: Option<String> },
+    /// Repays the auctioned debt and pays its collateral at the current
+    /// auction price to `recipient`.
+    Bid { auction_id: u64, recipient: Option<String> },
+    /// Adds the sent tokens to the reserve that pays `ClaimRewards`.
+    FundRewards {},
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     GetConfig {},
-    GetUserInfo { address: Addr },
+    /// Unknown addresses get an empty position.
+    GetUserInfo { address: String },
     GetPoolInfo {},
-}
\ No newline at end of file
+    GetUserLocks { address: String },
+    GetUnbonding { address: String },
+    GetLoan { loan_id: u64 },
+    GetUserLoans { address: String },
+    GetAuction { auction_id: u64 },
+    /// Open auctions, oldest first.
+    GetAuctions {},
+    GetRates {},
+    GetPendingRewards { address: String },
+    /// Previews `Borrow` from `address` without executing it.
+    SimulateBorrow { address: String, amount: Uint128, rate_mode: Option<RateMode> },
+    SimulateUnstake { address: String, amount: Uint128 },
+    SimulateRepay { address: String, amount: Uint128 },
+    /// Previews a `Liquidate` hook repaying `amount` of `borrower`'s debt.
+    SimulateLiquidation { borrower: String, amount: Uint128 },
+    /// Queued config changes, oldest first.
+    GetPendingChanges {},
+    /// Debt token balance, in the CW20 `BalanceResponse` shape.
+    Balance { address: String },
+    /// Debt token info, in the CW20 `TokenInfoResponse` shape. `total_supply`
+    /// is `PoolInfo.total_borrowed` accrued to the current block.
+    TokenInfo {},
+    /// Remaining debt delegation from `owner` to `spender`, in the CW20
+    /// `AllowanceResponse` shape.
+    Allowance { owner: String, spender: String },
+    /// Borrowed OM held for `address` after its transfer failed, in the CW20
+    /// `BalanceResponse` shape.
+    GetUnclaimedPayout { address: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateMsg {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ConfigResponse {
+    pub owner: String,
+    pub usd_token: String,
+    pub om_token: String,
+    pub collateral_ratio: Uint128,
+    pub interest_rate: Uint128,
+    pub penalty_rate: Uint128,
+    pub rate_slope: Uint128,
+    pub rebalance_utilization: Uint128,
+    pub unbonding_period: u64,
+    pub guardian: String,
+    pub timelock_delay: u64,
+    pub auction_start_price: Decimal,
+    pub auction_step: Decimal,
+    pub liquidation_bonus: Uint128,
+    pub close_factor: Uint128,
+    pub receipt_token: Option<String>,
+}
+
+/// A position with interest accrued to the current block. `borrow_limit` is
+/// the most the collateral supports at `Config.collateral_ratio`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UserInfoResponse {
+    pub staked_amount: Uint128,
+    pub locked_amount: Uint128,
+    pub boosted_amount: Uint128,
+    pub borrowed_amount: Uint128,
+    pub fixed_borrowed: Uint128,
+    pub rate_mode: RateMode,
+    pub stable_rate: Uint128,
+    pub total_debt: Uint128,
+    pub borrow_limit: Uint128,
+    pub available_to_borrow: Uint128,
+}
+
+/// Pool totals with interest accrued to the current block.
+/// `available_liquidity` is staked funds not lent out.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PoolInfoResponse {
+    pub total_staked: Uint128,
+    pub total_borrowed: Uint128,
+    pub total_boost: Uint128,
+    pub variable_borrowed: Uint128,
+    pub stable_borrowed: Uint128,
+    pub borrow_index: Decimal,
+    pub utilization: Decimal,
+    pub available_liquidity: Uint128,
+}
+
+/// Outcome of a `Simulate*` query. Rates are in percent per year.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SimulationResponse {
+    /// The `ContractError` the action would fail with, if any.
+    pub error: Option<String>,
+    /// The position after the action, or as it stands if the action fails.
+    pub position: UserInfoResponse,
+    /// `borrow_limit / total_debt`. Below one the position can be
+    /// liquidated; `None` without debt.
+    pub health_factor: Option<Decimal>,
+    pub variable_rate_before: Uint128,
+    pub variable_rate_after: Uint128,
+}
+
+/// Rates are in percent per year, matching `Config.interest_rate`. New stable
+/// and fixed-term borrows lock in `variable_rate`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RatesResponse {
+    pub utilization: Decimal,
+    pub variable_rate: Uint128,
+    pub average_stable_rate: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingRewardsResponse {
+    /// The current reward token, once rewards are set up.
+    pub reward_token: Option<String>,
+    /// Earned in the current reward token.
+    pub pending: Uint128,
+    /// Earned in reward tokens used before, still claimable.
+    pub settled: Vec<Cw20Coin>,
+}
+
+/// A fixed-term loan. `rate` is locked at origination.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LoanResponse {
+    pub id: u64,
+    pub borrower: String,
+    pub principal: Uint128,
+    pub rate: Uint128,
+    pub start_time: u64,
+    pub maturity: u64,
+}
+
+/// `multiplier` is the reward boost in percent.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LockResponse {
+    pub id: u64,
+    pub amount: Uint128,
+    pub multiplier: Uint128,
+    pub unlock_time: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UnbondingResponse {
+    pub id: u64,
+    pub amount: Uint128,
+    pub release_time: u64,
+}
+
+/// `payout` is the collateral a bid in the current block would receive.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AuctionResponse {
+    pub id: u64,
+    pub borrower: String,
+    pub debt: Uint128,
+    pub collateral: Uint128,
+    pub start_time: u64,
+    pub start_price: Decimal,
+    pub step: Decimal,
+    pub price: Decimal,
+    pub payout: Uint128,
+}
+
+/// A queued `ConfigChange`, executable by anyone from `eta` onwards.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingChangeResponse {
+    pub id: u64,
+    pub change: ConfigChange,
+    pub eta: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::from_json;
+
+    #[test]
+    fn cw20_hook_defaults_optional_addresses() {
+        assert_eq!(
+            from_json::<Cw20HookMsg>(br#"{"stake":{}}"#).unwrap(),
+            Cw20HookMsg::Stake { on_behalf_of: None }
+        );
+        assert_eq!(
+            from_json::<Cw20HookMsg>(br#"{"repay":{}}"#).unwrap(),
+            Cw20HookMsg::Repay { on_behalf_of: None }
+        );
+        assert_eq!(
+            from_json::<Cw20HookMsg>(br#"{"liquidate":{"borrower":"bob"}}"#).unwrap(),
+            Cw20HookMsg::Liquidate { borrower: "bob".to_string(), recipient: None }
+        );
+        assert_eq!(
+            from_json::<Cw20HookMsg>(br#"{"bid":{"auction_id":1}}"#).unwrap(),
+            Cw20HookMsg::Bid { auction_id: 1, recipient: None }
+        );
+        assert_eq!(
+            from_json::<Cw20HookMsg>(br#"{"stake_locked":{"duration":60}}"#).unwrap(),
+            Cw20HookMsg::StakeLocked { duration: 60, on_behalf_of: None }
+        );
+        assert_eq!(
+            from_json::<Cw20HookMsg>(br#"{"liquidate_loan":{"loan_id":1}}"#).unwrap(),
+            Cw20HookMsg::LiquidateLoan { loan_id: 1, recipient: None }
+        );
+    }
+
+    #[test]
+    fn cw20_hook_reads_optional_addresses() {
+        assert_eq!(
+            from_json::<Cw20HookMsg>(br#"{"stake":{"on_behalf_of":"bob"}}"#).unwrap(),
+            Cw20HookMsg::Stake { on_behalf_of: Some("bob".to_string()) }
+        );
+        assert_eq!(
+            from_json::<Cw20HookMsg>(br#"{"repay":{"on_behalf_of":"bob"}}"#).unwrap(),
+            Cw20HookMsg::Repay { on_behalf_of: Some("bob".to_string()) }
+        );
+        assert_eq!(
+            from_json::<Cw20HookMsg>(br#"{"liquidate":{"borrower":"bob","recipient":"carol"}}"#)
+                .unwrap(),
+            Cw20HookMsg::Liquidate {
+                borrower: "bob".to_string(),
+                recipient: Some("carol".to_string()),
+            }
+        );
+        assert_eq!(
+            from_json::<Cw20HookMsg>(br#"{"bid":{"auction_id":1,"recipient":"carol"}}"#).unwrap(),
+            Cw20HookMsg::Bid { auction_id: 1, recipient: Some("carol".to_string()) }
+        );
+        assert_eq!(
+            from_json::<Cw20HookMsg>(br#"{"stake_locked":{"duration":60,"on_behalf_of":"bob"}}"#)
+                .unwrap(),
+            Cw20HookMsg::StakeLocked { duration: 60, on_behalf_of: Some("bob".to_string()) }
+        );
+        assert_eq!(
+            from_json::<Cw20HookMsg>(br#"{"liquidate_loan":{"loan_id":1,"recipient":"carol"}}"#)
+                .unwrap(),
+            Cw20HookMsg::LiquidateLoan { loan_id: 1, recipient: Some("carol".to_string()) }
+        );
+    }
+
+    #[test]
+    fn cw20_hook_reads_loan_repayment() {
+        assert_eq!(
+            from_json::<Cw20HookMsg>(br#"{"repay_loan":{"loan_id":1}}"#).unwrap(),
+            Cw20HookMsg::RepayLoan { loan_id: 1 }
+        );
+    }
+
+    #[test]
+    fn cw20_hook_rejects_execute_only_messages() {
+        assert!(from_json::<Cw20HookMsg>(br#"{"borrow":{"amount":"1"}}"#).is_err());
+        assert!(from_json::<Cw20HookMsg>(br#"{"claim_rewards":{}}"#).is_err());
+        assert!(from_json::<Cw20HookMsg>(br#"{"borrow_fixed":{"amount":"1","term_seconds":1}}"#)
+            .is_err());
+        assert!(from_json::<Cw20HookMsg>(
+            br#"{"receive":{"sender":"bob","amount":"1","msg":"e30="}}"#
+        )
+        .is_err());
+    }
+}
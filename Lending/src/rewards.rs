@@ -0,0 +1,66 @@
+use cosmwasm_std::{Decimal, Uint128};
+
+use crate::error::ContractError;
+use crate::math::{mul_decimal, ratio, Rounding};
+use crate::state::{PoolInfo, RewardState, UserInfo, UserRewards};
+
+/// Advances the reward-per-unit indices to `now`. Emissions for a side with
+/// nothing staked or borrowed are skipped rather than carried forward.
+pub fn update_reward_indices(
+    state: &mut RewardState,
+    pool: &PoolInfo,
+    now: u64,
+) -> Result<(), ContractError> {
+    let elapsed = Uint128::from(now.saturating_sub(state.last_update));
+    let supply_weight = pool.total_staked.checked_add(pool.total_boost)?;
+    if !supply_weight.is_zero() {
+        let emitted = state.supply_rate.checked_mul(elapsed)?;
+        state.supply_index = state
+            .supply_index
+            .checked_add(ratio(emitted, supply_weight)?)?;
+    }
+    if !pool.total_borrowed.is_zero() {
+        let emitted = state.borrow_rate.checked_mul(elapsed)?;
+        state.borrow_index = state
+            .borrow_index
+            .checked_add(ratio(emitted, pool.total_borrowed)?)?;
+    }
+    state.last_update = now;
+    Ok(())
+}
+
+/// Credits `rewards` with what `user` earned since its last checkpoint, using
+/// its stored deposit (plus lock boost) and debt as weights. `expired` holds
+/// the boost of each expired lock still on the position with the supply index
+/// at its unlock time; that boost earns nothing past it. Rewards are paid
+/// out, so they round down.
+pub fn update_user_rewards(
+    state: &RewardState,
+    rewards: &mut UserRewards,
+    user: &UserInfo,
+    expired: &[(Uint128, Decimal)],
+) -> Result<(), ContractError> {
+    let mut supply = user.staked_amount.checked_add(user.boosted_amount)?;
+    let mut supply_earned = Uint128::zero();
+    for (boost, index) in expired {
+        supply = supply.checked_sub(*boost)?;
+        if *index > rewards.supply_index {
+            supply_earned = supply_earned.checked_add(earned(*boost, *index, rewards.supply_index)?)?;
+        }
+    }
+    let debt = user.borrowed_amount.checked_add(user.fixed_borrowed)?;
+    let supply_earned =
+        supply_earned.checked_add(earned(supply, state.supply_index, rewards.supply_index)?)?;
+    let borrow_earned = earned(debt, state.borrow_index, rewards.borrow_index)?;
+    rewards.pending = rewards
+        .pending
+        .checked_add(supply_earned)?
+        .checked_add(borrow_earned)?;
+    rewards.supply_index = state.supply_index;
+    rewards.borrow_index = state.borrow_index;
+    Ok(())
+}
+
+fn earned(weight: Uint128, index: Decimal, checkpoint: Decimal) -> Result<Uint128, ContractError> {
+    mul_decimal(weight, index.checked_sub(checkpoint)?, Rounding::Down)
+}
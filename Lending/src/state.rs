@@ -1,8 +1,13 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use cosmwasm_std::{Addr, Uint128};
+
+use cosmwasm_std::{Addr, Api, Decimal, Empty, Uint128};
 use cw_storage_plus::{Item, Map};
 
+use crate::error::ContractError;
+use crate::msg::{ConfigChange, RateMode};
+use crate::math::{mul_decimal, mul_decimals, mul_ratio, Rounding};
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Config {
     pub owner: Addr,
@@ -10,21 +15,288 @@ pub struct Config {
     pub om_token: Addr,
     pub collateral_ratio: Uint128,
     pub interest_rate: Uint128,
+    pub penalty_rate: Uint128,
+    pub rate_slope: Uint128,
+    pub rebalance_utilization: Uint128,
+    /// Seconds between `Unstake` and the funds becoming claimable. Zero pays
+    /// out immediately.
+    pub unbonding_period: u64,
+    /// Can cancel queued config changes.
+    pub guardian: Addr,
+    /// Seconds a queued config change waits before it can be executed.
+    pub timelock_delay: u64,
+    /// USD collateral paid per unit of OM debt when an auction opens.
+    pub auction_start_price: Decimal,
+    /// Increase of the auction price per second.
+    pub auction_step: Decimal,
+    /// Extra collateral a `Liquidate` hook seizes, in percent of the debt
+    /// repaid.
+    pub liquidation_bonus: Uint128,
+    /// Most of a position's open-ended debt one `Liquidate` hook may repay,
+    /// in percent.
+    pub close_factor: Uint128,
+    /// CW20 receipt minted 1:1 against `UserInfo.staked_amount`. Set once the
+    /// token instantiated by this contract replies.
+    pub receipt_token: Option<Addr>,
+}
+
+impl Config {
+    /// Rejects parameters that would break borrowing, auctions or the
+    /// timelock.
+    pub fn validate(&self) -> Result<(), ContractError> {
+        let invalid = |field: &str| Err(ContractError::InvalidConfig { field: field.to_string() });
+        if self.collateral_ratio.is_zero() || self.collateral_ratio > Uint128::new(100) {
+            return invalid("collateral_ratio");
+        }
+        if self.auction_start_price.is_zero() {
+            return invalid("auction_start_price");
+        }
+        if self.auction_step.is_zero() {
+            return invalid("auction_step");
+        }
+        if self.close_factor.is_zero() || self.close_factor > Uint128::new(100) {
+            return invalid("close_factor");
+        }
+        if self.timelock_delay == 0 {
+            return invalid("timelock_delay");
+        }
+        Ok(())
+    }
+
+    /// Applies the fields set in `change` and validates the result.
+    pub fn apply(&mut self, api: &dyn Api, change: &ConfigChange) -> Result<(), ContractError> {
+        if let Some(collateral_ratio) = change.collateral_ratio {
+            self.collateral_ratio = collateral_ratio;
+        }
+        if let Some(interest_rate) = change.interest_rate {
+            self.interest_rate = interest_rate;
+        }
+        if let Some(penalty_rate) = change.penalty_rate {
+            self.penalty_rate = penalty_rate;
+        }
+        if let Some(rate_slope) = change.rate_slope {
+            self.rate_slope = rate_slope;
+        }
+        if let Some(rebalance_utilization) = change.rebalance_utilization {
+            self.rebalance_utilization = rebalance_utilization;
+        }
+        if let Some(unbonding_period) = change.unbonding_period {
+            self.unbonding_period = unbonding_period;
+        }
+        if let Some(auction_start_price) = change.auction_start_price {
+            self.auction_start_price = auction_start_price;
+        }
+        if let Some(auction_step) = change.auction_step {
+            self.auction_step = auction_step;
+        }
+        if let Some(liquidation_bonus) = change.liquidation_bonus {
+            self.liquidation_bonus = liquidation_bonus;
+        }
+        if let Some(close_factor) = change.close_factor {
+            self.close_factor = close_factor;
+        }
+        if let Some(guardian) = &change.guardian {
+            self.guardian = api.addr_validate(guardian)?;
+        }
+        if let Some(timelock_delay) = change.timelock_delay {
+            self.timelock_delay = timelock_delay;
+        }
+        self.validate()
+    }
 }
 
+/// `locked_amount` is the part of `staked_amount` held by unexpired locks, and
+/// `boosted_amount` the extra reward weight those locks earn.
+/// `borrowed_amount` is the open-ended debt as of `last_interaction`. It grows
+/// with the pool borrow index in variable mode, or at `stable_rate` in stable mode.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
 pub struct UserInfo {
     pub staked_amount: Uint128,
+    pub locked_amount: Uint128,
+    pub boosted_amount: Uint128,
     pub borrowed_amount: Uint128,
+    pub fixed_borrowed: Uint128,
+    pub rate_mode: RateMode,
+    pub stable_rate: Uint128,
+    pub borrow_index: Decimal,
     pub last_interaction: u64,
 }
 
+/// `total_borrowed` covers variable, stable and fixed-term debt. `total_boost`
+/// is the boost of unexpired locks.
+/// `stable_weighted_rate` is the sum of stable debt times its rate, used for
+/// the average stable rate.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct PoolInfo {
     pub total_staked: Uint128,
     pub total_borrowed: Uint128,
+    pub total_boost: Uint128,
+    pub variable_borrowed: Uint128,
+    pub stable_borrowed: Uint128,
+    pub stable_weighted_rate: Uint128,
+    pub borrow_index: Decimal,
+    pub last_accrual: u64,
+}
+
+/// A fixed-term loan. `rate` is locked at origination; once `maturity` has
+/// passed, `Config.penalty_rate` is charged on top of it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Loan {
+    pub id: u64,
+    pub borrower: Addr,
+    pub principal: Uint128,
+    pub rate: Uint128,
+    pub start_time: u64,
+    pub maturity: u64,
+}
+
+/// A time-locked deposit. `multiplier` is the reward boost in percent; the
+/// boost earns rewards until `unlock_time`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Lock {
+    pub id: u64,
+    pub amount: Uint128,
+    pub multiplier: Uint128,
+    pub unlock_time: u64,
+}
+
+impl Lock {
+    /// Reward weight earned on top of the locked amount itself.
+    pub fn boost(&self) -> Result<Uint128, ContractError> {
+        let bonus = self.multiplier.saturating_sub(Uint128::new(100));
+        mul_ratio(self.amount, bonus, 100u128, Rounding::Down)
+    }
+}
+
+/// Locks sharing an unlock time. Their `boost` leaves `PoolInfo.total_boost`
+/// at the first checkpoint from that time on, which records the reward
+/// `supply_index` reached at the unlock time. Dropped once every one of the
+/// `locks` is released.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct LockExpiry {
+    pub boost: Uint128,
+    pub locks: u64,
+    /// Set once the unlock time has passed.
+    pub supply_index: Option<Decimal>,
+    /// `RewardState.epoch` at the unlock time, set with `supply_index`.
+    #[serde(default)]
+    pub epoch: u64,
+}
+
+/// A withdrawal waiting out `Config.unbonding_period`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Unbonding {
+    pub id: u64,
+    pub amount: Uint128,
+    pub release_time: u64,
+}
+
+/// Collateral seized from an unhealthy position, sold for its open debt. The
+/// price in USD per OM starts at `start_price` and rises by `step` every
+/// second, so the bidder's discount grows until someone takes it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Auction {
+    pub id: u64,
+    pub borrower: Addr,
+    pub debt: Uint128,
+    pub collateral: Uint128,
+    pub start_time: u64,
+    pub start_price: Decimal,
+    pub step: Decimal,
+}
+
+impl Auction {
+    pub fn price(&self, now: u64) -> Result<Decimal, ContractError> {
+        let elapsed = Decimal::from_ratio(now.saturating_sub(self.start_time), 1u64);
+        Ok(self.start_price.checked_add(mul_decimals(self.step, elapsed)?)?)
+    }
+
+    /// Collateral a bid at `now` receives, rounded down; the rest goes back to
+    /// the borrower.
+    pub fn payout(&self, now: u64) -> Result<Uint128, ContractError> {
+        // Once `debt * price` exceeds the collateral the payout is capped, even
+        // if the product itself no longer fits.
+        let payout = self.price(now).and_then(|price| mul_decimal(self.debt, price, Rounding::Down));
+        match payout {
+            Ok(payout) => Ok(payout.min(self.collateral)),
+            Err(ContractError::Overflow(_)) => Ok(self.collateral),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// Liquidity mining emissions. `supply_rate` and `borrow_rate` are reward
+/// tokens per second, split pro rata over boosted `PoolInfo.total_staked` and
+/// `PoolInfo.total_borrowed`. The indices are rewards accrued per unit.
+/// Changing `reward_token` closes the current `epoch` into `REWARD_EPOCHS`
+/// and restarts the indices at zero.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RewardState {
+    pub reward_token: Addr,
+    pub supply_rate: Uint128,
+    pub borrow_rate: Uint128,
+    pub supply_index: Decimal,
+    pub borrow_index: Decimal,
+    pub last_update: u64,
+    #[serde(default)]
+    pub epoch: u64,
+}
+
+/// A reward epoch closed by a token switch, with its final indices.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RewardEpoch {
+    pub reward_token: Addr,
+    pub supply_index: Decimal,
+    pub borrow_index: Decimal,
+}
+
+/// `pending` is earned in the reward token of `epoch`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct UserRewards {
+    pub pending: Uint128,
+    pub supply_index: Decimal,
+    pub borrow_index: Decimal,
+    #[serde(default)]
+    pub epoch: u64,
+}
+
+/// A queued `ConfigChange`, executable by anyone from `eta` onwards.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingChange {
+    pub id: u64,
+    pub change: ConfigChange,
+    pub eta: u64,
 }
 
 pub const CONFIG: Item<Config> = Item::new("config");
 pub const USERS: Map<&Addr, UserInfo> = Map::new("users");
-pub const POOL: Item<PoolInfo> = Item::new("pool");
\ No newline at end of file
+pub const POOL: Item<PoolInfo> = Item::new("pool");
+pub const LOAN_COUNT: Item<u64> = Item::new("loan_count");
+pub const LOANS: Map<u64, Loan> = Map::new("loans");
+pub const USER_LOANS: Map<(&Addr, u64), Empty> = Map::new("user_loans");
+/// Debt delegations, keyed by (delegator, delegatee).
+pub const DEBT_ALLOWANCES: Map<(&Addr, &Addr), Uint128> = Map::new("debt_allowances");
+pub const REWARD_STATE: Item<RewardState> = Item::new("reward_state");
+pub const USER_REWARDS: Map<&Addr, UserRewards> = Map::new("user_rewards");
+pub const REWARD_EPOCHS: Map<u64, RewardEpoch> = Map::new("reward_epochs");
+/// Rewards from closed epochs, keyed by (user, reward token).
+pub const SETTLED_REWARDS: Map<(&Addr, &Addr), Uint128> = Map::new("settled_rewards");
+/// Reward tokens funded through `Cw20HookMsg::FundRewards` and not yet
+/// claimed. Reserved OM is not lendable.
+pub const REWARD_RESERVES: Map<&Addr, Uint128> = Map::new("reward_reserves");
+pub const LOCK_COUNT: Item<u64> = Item::new("lock_count");
+pub const LOCKS: Map<(&Addr, u64), Lock> = Map::new("locks");
+/// Keyed by unlock time.
+pub const LOCK_EXPIRIES: Map<u64, LockExpiry> = Map::new("lock_expiries");
+pub const UNBONDING_COUNT: Item<u64> = Item::new("unbonding_count");
+pub const UNBONDING: Map<(&Addr, u64), Unbonding> = Map::new("unbonding");
+pub const PENDING_CHANGE_COUNT: Item<u64> = Item::new("pending_change_count");
+pub const PENDING_CHANGES: Map<u64, PendingChange> = Map::new("pending_changes");
+pub const AUCTION_COUNT: Item<u64> = Item::new("auction_count");
+pub const AUCTIONS: Map<u64, Auction> = Map::new("auctions");
+/// OM from borrows made while the token was paused or had blocked the
+/// borrower. Claimable through `ClaimPayout`.
+pub const UNCLAIMED_PAYOUTS: Map<&Addr, Uint128> = Map::new("unclaimed_payouts");
+/// Sum of `UNCLAIMED_PAYOUTS`. This OM stays in the contract but is no
+/// longer lendable.
+pub const UNCLAIMED_TOTAL: Item<Uint128> = Item::new("unclaimed_total");
@@ -0,0 +1,45 @@
+use std::env::current_dir;
+use std::fs::create_dir_all;
+
+use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
+use cw20::{
+    AllAccountsResponse, AllAllowancesResponse, AllowanceResponse, BalanceResponse,
+    DownloadLogoResponse, MarketingInfoResponse, MinterResponse, TokenInfoResponse,
+};
+
+use token::msg::{
+    AllBlockedResponse, AllMintersResponse, BlockedResponse, ComplianceResponse,
+    ControllerHookMsg, ExecuteMsg, InstantiateMsg, MigrateMsg, MinterAllowance, QueryMsg,
+    PermitNonceResponse, PermitPayload, TotalSupplyResponse, VestingResponse,
+};
+
+fn main() {
+    let mut out_dir = current_dir().unwrap();
+    out_dir.push("schema");
+    create_dir_all(&out_dir).unwrap();
+    remove_schemas(&out_dir).unwrap();
+
+    export_schema(&schema_for!(InstantiateMsg), &out_dir);
+    export_schema(&schema_for!(ExecuteMsg), &out_dir);
+    export_schema(&schema_for!(QueryMsg), &out_dir);
+    export_schema(&schema_for!(MigrateMsg), &out_dir);
+    export_schema(&schema_for!(ControllerHookMsg), &out_dir);
+
+    export_schema(&schema_for!(BalanceResponse), &out_dir);
+    export_schema(&schema_for!(TokenInfoResponse), &out_dir);
+    export_schema(&schema_for!(MinterResponse), &out_dir);
+    export_schema(&schema_for!(AllowanceResponse), &out_dir);
+    export_schema(&schema_for!(AllAllowancesResponse), &out_dir);
+    export_schema(&schema_for!(AllAccountsResponse), &out_dir);
+    export_schema(&schema_for!(MarketingInfoResponse), &out_dir);
+    export_schema(&schema_for!(DownloadLogoResponse), &out_dir);
+    export_schema(&schema_for!(MinterAllowance), &out_dir);
+    export_schema(&schema_for!(AllMintersResponse), &out_dir);
+    export_schema(&schema_for!(ComplianceResponse), &out_dir);
+    export_schema(&schema_for!(BlockedResponse), &out_dir);
+    export_schema(&schema_for!(AllBlockedResponse), &out_dir);
+    export_schema(&schema_for!(TotalSupplyResponse), &out_dir);
+    export_schema(&schema_for!(PermitNonceResponse), &out_dir);
+    export_schema(&schema_for!(PermitPayload), &out_dir);
+    export_schema(&schema_for!(VestingResponse), &out_dir);
+}
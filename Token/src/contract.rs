@@ -1,116 +1,720 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult,
+    to_json_binary, to_json_vec, Addr, Binary, Deps, DepsMut, Empty, Env, MessageInfo, Order,
+    Response, StdError, StdResult, Storage, SubMsg, Uint128, WasmMsg,
 };
-use cw20_base::ContractError;
+use bech32::ToBase32;
+use cw20::{AllowanceResponse, BalanceResponse, MinterResponse};
+use ripemd::Ripemd160;
+use sha2::{Digest, Sha256};
+use cw_storage_plus::Bound;
 use cw20_base::enumerable::{query_all_allowances, query_all_accounts};
-use cw20_base::msg::{QueryMsg,ExecuteMsg};
+use cw20_base::state::{ALLOWANCES, BALANCES, TOKEN_INFO};
 
-use crate::msg::MigrateMsg;
+use crate::error::ContractError;
+use crate::msg::{
+    AllBlockedResponse, AllMintersResponse, BlockedResponse, ComplianceResponse,
+    ControllerHookMsg, ExecuteMsg, InstantiateMsg, MigrateMsg, MinterAllowance, Permit,
+    PermitNonceResponse, PermitPayload, QueryMsg, TotalSupplyResponse, VestingGrantResponse,
+    VestingResponse,
+};
+use crate::state::{
+    VestingGrant, VestingSchedule, BALANCE_SNAPSHOTS, BLOCKED, CAP, CONTROLLER, MINTERS, OWNER,
+    PAUSED, PERMIT_NONCES, SUPPLY_SNAPSHOTS, VESTING,
+};
 use cw2::set_contract_version;
 use cw20_base::allowances::{
     execute_decrease_allowance, execute_increase_allowance, execute_send_from,
     execute_transfer_from, query_allowance, execute_burn_from,
 };
 use cw20_base::contract::{
-    execute_mint, execute_send, execute_transfer, execute_update_marketing,
-    execute_upload_logo, query_balance, query_token_info, query_minter, query_download_logo, query_marketing_info, execute_burn,
+    execute_send, execute_transfer, execute_update_marketing,
+    execute_upload_logo, query_balance, query_token_info, query_download_logo, query_marketing_info, execute_burn,
 };
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:cw20-token";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+// settings for pagination
+const MAX_LIMIT: u32 = 30;
+const DEFAULT_LIMIT: u32 = 10;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    msg: cw20_base::msg::InstantiateMsg,
+    msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
+    if let Some(controller) = msg.controller {
+        CONTROLLER.save(deps.storage, &deps.api.addr_validate(&controller)?)?;
+    }
+    OWNER.save(deps.storage, &info.sender)?;
+    if let Some(mint) = &msg.base.mint {
+        MINTERS.save(deps.storage, &deps.api.addr_validate(&mint.minter)?, &None)?;
+        if let Some(cap) = mint.cap {
+            CAP.save(deps.storage, &cap)?;
+        }
+    }
+
+    let height = env.block.height;
+    let holders = msg
+        .base
+        .initial_balances
+        .iter()
+        .map(|coin| deps.api.addr_validate(&coin.address))
+        .collect::<StdResult<Vec<_>>>()?;
+
     /* Execute the instantiate method from cw_20_base as the code from that
     library is already battle tested we do not have to re-write the full
     functionality: https://github.com/CosmWasm/cw-plus/tree/main/contracts/cw20-base*/
-    Ok(cw20_base::contract::instantiate(deps, env, info, msg)?)
+    let res = cw20_base::contract::instantiate(deps.branch(), env, info, msg.base)?;
+    snapshot_balances(deps.storage, height, &holders)?;
+    snapshot_supply(deps.storage, height)?;
+    Ok(res)
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
     msg: ExecuteMsg,
-) -> Result<Response, cw20_base::ContractError> {
+) -> Result<Response, ContractError> {
+    let height = env.block.height;
+    let now = env.block.time.seconds();
     match msg {
         ExecuteMsg::Transfer { recipient, amount } => {
-            execute_transfer(deps, env, info, recipient, amount)
+            check_transfer(deps.as_ref(), &[info.sender.as_str(), &recipient])?;
+            let hook = controller_hook(deps.as_ref(), &info.sender, &recipient, amount)?;
+            let parties = [info.sender.clone(), deps.api.addr_validate(&recipient)?];
+            check_vesting(deps.storage, &parties[0], amount, now)?;
+            let res = execute_transfer(deps.branch(), env, info, recipient, amount)?;
+            snapshot_balances(deps.storage, height, &parties)?;
+            Ok(prepend_hook(res, hook))
+        }
+        ExecuteMsg::Burn { amount } => {
+            check_burn(deps.as_ref(), &info.sender)?;
+            let parties = [info.sender.clone()];
+            check_vesting(deps.storage, &parties[0], amount, now)?;
+            let res = execute_burn(deps.branch(), env, info, amount)?;
+            snapshot_balances(deps.storage, height, &parties)?;
+            snapshot_supply(deps.storage, height)?;
+            Ok(res)
         }
-        ExecuteMsg::Burn { amount } => execute_burn(deps, env, info, amount),
         ExecuteMsg::Send {
             contract,
             amount,
             msg,
-        } => execute_send(deps, env, info, contract, amount, msg),
-        ExecuteMsg::Mint { recipient, amount } => execute_mint(deps, env, info, recipient, amount),
+        } => {
+            check_transfer(deps.as_ref(), &[info.sender.as_str(), &contract])?;
+            let hook = controller_hook(deps.as_ref(), &info.sender, &contract, amount)?;
+            let parties = [info.sender.clone(), deps.api.addr_validate(&contract)?];
+            check_vesting(deps.storage, &parties[0], amount, now)?;
+            let res = execute_send(deps.branch(), env, info, contract, amount, msg)?;
+            snapshot_balances(deps.storage, height, &parties)?;
+            Ok(prepend_hook(res, hook))
+        }
+        ExecuteMsg::Mint { recipient, amount } => {
+            check_transfer(deps.as_ref(), &[info.sender.as_str(), &recipient])?;
+            let parties = [deps.api.addr_validate(&recipient)?];
+            let res = execute_mint(deps.branch(), info, recipient, amount)?;
+            snapshot_balances(deps.storage, height, &parties)?;
+            snapshot_supply(deps.storage, height)?;
+            Ok(res)
+        }
+        ExecuteMsg::MintVested {
+            recipient,
+            amount,
+            schedule,
+        } => {
+            check_transfer(deps.as_ref(), &[info.sender.as_str(), &recipient])?;
+            let parties = [deps.api.addr_validate(&recipient)?];
+            let res = execute_mint(deps.branch(), info, recipient, amount)?;
+            let schedule = VestingSchedule {
+                start_time: schedule.start_time,
+                cliff_time: schedule.cliff_time,
+                end_time: schedule.end_time,
+            };
+            add_vesting(deps.storage, &parties[0], amount, schedule, now)?;
+            snapshot_balances(deps.storage, height, &parties)?;
+            snapshot_supply(deps.storage, height)?;
+            Ok(res.add_attribute("vested", "true"))
+        }
         ExecuteMsg::IncreaseAllowance {
             spender,
             amount,
             expires,
-        } => execute_increase_allowance(deps, env, info, spender, amount, expires),
+        } => Ok(execute_increase_allowance(deps, env, info, spender, amount, expires)?),
         ExecuteMsg::DecreaseAllowance {
             spender,
             amount,
             expires,
-        } => execute_decrease_allowance(deps, env, info, spender, amount, expires),
+        } => Ok(execute_decrease_allowance(deps, env, info, spender, amount, expires)?),
         ExecuteMsg::TransferFrom {
             owner,
             recipient,
             amount,
-        } => execute_transfer_from(deps, env, info, owner, recipient, amount),
-        ExecuteMsg::BurnFrom { owner, amount } => execute_burn_from(deps, env, info, owner, amount),
+        } => {
+            check_transfer(deps.as_ref(), &[info.sender.as_str(), &owner, &recipient])?;
+            let owner_addr = deps.api.addr_validate(&owner)?;
+            let hook = controller_hook(deps.as_ref(), &owner_addr, &recipient, amount)?;
+            let parties = [owner_addr, deps.api.addr_validate(&recipient)?];
+            check_vesting(deps.storage, &parties[0], amount, now)?;
+            let res = execute_transfer_from(deps.branch(), env, info, owner, recipient, amount)?;
+            snapshot_balances(deps.storage, height, &parties)?;
+            Ok(prepend_hook(res, hook))
+        }
+        ExecuteMsg::BurnFrom { owner, amount } => {
+            let controlled = check_burn(deps.as_ref(), &info.sender)?;
+            let parties = [deps.api.addr_validate(&owner)?];
+            // The controller burns receipts on withdrawal without needing an allowance.
+            check_vesting(deps.storage, &parties[0], amount, now)?;
+            let res = if controlled {
+                let owner_info = MessageInfo {
+                    sender: parties[0].clone(),
+                    funds: vec![],
+                };
+                execute_burn(deps.branch(), env, owner_info, amount)?
+            } else {
+                execute_burn_from(deps.branch(), env, info, owner, amount)?
+            };
+            snapshot_balances(deps.storage, height, &parties)?;
+            snapshot_supply(deps.storage, height)?;
+            Ok(res)
+        }
         ExecuteMsg::SendFrom {
             owner,
             contract,
             amount,
             msg,
-        } => execute_send_from(deps, env, info, owner, contract, amount, msg),
+        } => {
+            check_transfer(deps.as_ref(), &[info.sender.as_str(), &owner, &contract])?;
+            let owner_addr = deps.api.addr_validate(&owner)?;
+            let hook = controller_hook(deps.as_ref(), &owner_addr, &contract, amount)?;
+            let parties = [owner_addr, deps.api.addr_validate(&contract)?];
+            check_vesting(deps.storage, &parties[0], amount, now)?;
+            let res = execute_send_from(deps.branch(), env, info, owner, contract, amount, msg)?;
+            snapshot_balances(deps.storage, height, &parties)?;
+            Ok(prepend_hook(res, hook))
+        }
         ExecuteMsg::UpdateMarketing {
             project,
             description,
             marketing,
-        } => execute_update_marketing(deps, env, info, project, description, marketing),
-        ExecuteMsg::UploadLogo(logo) => execute_upload_logo(deps, env, info, logo),
+        } => Ok(execute_update_marketing(deps, env, info, project, description, marketing)?),
+        ExecuteMsg::UploadLogo(logo) => Ok(execute_upload_logo(deps, env, info, logo)?),
+        ExecuteMsg::UpdateMinters { set, remove } => execute_update_minters(deps, info, set, remove),
+        ExecuteMsg::SetPaused { paused } => execute_set_paused(deps, info, paused),
+        ExecuteMsg::UpdateBlocklist { add, remove } => {
+            execute_update_blocklist(deps, info, add, remove)
+        }
+        ExecuteMsg::UpdateOwner { owner } => execute_update_owner(deps, info, owner),
+        ExecuteMsg::Permit(permit) => execute_permit(deps, env, permit),
     }
 }
 
+/// Rejects the transfer if tokens are paused or any party is blocked.
+fn check_transfer(deps: Deps, parties: &[&str]) -> Result<(), ContractError> {
+    if PAUSED.may_load(deps.storage)?.unwrap_or(false) {
+        return Err(ContractError::Paused {});
+    }
+    for party in parties {
+        let address = deps.api.addr_validate(party)?;
+        if BLOCKED.has(deps.storage, &address) {
+            return Err(ContractError::Blocked {
+                address: address.into(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Rejects burns by anyone but the controller, if this token has one, since
+/// the controller tracks the supply. Returns whether `sender` is the
+/// controller.
+fn check_burn(deps: Deps, sender: &Addr) -> Result<bool, ContractError> {
+    match CONTROLLER.may_load(deps.storage)? {
+        Some(controller) if controller == *sender => Ok(true),
+        Some(_) => Err(ContractError::ControlledBurn {}),
+        None => Ok(false),
+    }
+}
+
+/// Records a vested mint to `recipient`, dropping its fully released grants.
+fn add_vesting(
+    storage: &mut dyn Storage,
+    recipient: &Addr,
+    amount: Uint128,
+    schedule: VestingSchedule,
+    now: u64,
+) -> Result<(), ContractError> {
+    if !schedule.is_valid() {
+        return Err(ContractError::InvalidSchedule {});
+    }
+    let mut grants = VESTING.may_load(storage, recipient)?.unwrap_or_default();
+    grants.retain(|grant| !grant.locked(now).is_zero());
+    grants.push(VestingGrant { amount, schedule });
+    VESTING.save(storage, recipient, &grants)?;
+    Ok(())
+}
+
+fn locked_amount(storage: &dyn Storage, address: &Addr, now: u64) -> StdResult<Uint128> {
+    let grants = VESTING.may_load(storage, address)?.unwrap_or_default();
+    grants
+        .iter()
+        .try_fold(Uint128::zero(), |total, grant| Ok(total.checked_add(grant.locked(now))?))
+}
+
+/// Fails if debiting `amount` would leave `address` with less than its
+/// still-vesting tokens.
+fn check_vesting(
+    storage: &dyn Storage,
+    address: &Addr,
+    amount: Uint128,
+    now: u64,
+) -> Result<(), ContractError> {
+    let balance = BALANCES.may_load(storage, address)?.unwrap_or_default();
+    // An overdraft is left for cw20-base to reject.
+    let remaining = match balance.checked_sub(amount) {
+        Ok(remaining) => remaining,
+        Err(_) => return Ok(()),
+    };
+    let locked = locked_amount(storage, address, now)?;
+    if remaining < locked {
+        return Err(ContractError::TokensLocked { locked });
+    }
+    Ok(())
+}
+
+/// Copies the current balances of `addresses` into `BALANCE_SNAPSHOTS`.
+fn snapshot_balances(storage: &mut dyn Storage, height: u64, addresses: &[Addr]) -> StdResult<()> {
+    for address in addresses {
+        let balance = BALANCES.may_load(storage, address)?.unwrap_or_default();
+        BALANCE_SNAPSHOTS.save(storage, address, &balance, height)?;
+    }
+    Ok(())
+}
+
+/// Copies the current total supply into `SUPPLY_SNAPSHOTS`.
+fn snapshot_supply(storage: &mut dyn Storage, height: u64) -> StdResult<()> {
+    let total_supply = TOKEN_INFO.load(storage)?.total_supply;
+    SUPPLY_SNAPSHOTS.save(storage, &total_supply, height)
+}
+
+fn assert_owner(deps: Deps, sender: &Addr) -> Result<(), ContractError> {
+    if OWNER.may_load(deps.storage)?.as_ref() != Some(sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    Ok(())
+}
+
+/// Mints like cw20-base, but for any minter and within its allowance.
+pub fn execute_mint(
+    deps: DepsMut,
+    info: MessageInfo,
+    recipient: String,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    if amount.is_zero() {
+        return Err(ContractError::InvalidZeroAmount {});
+    }
+
+    let allowance = MINTERS
+        .may_load(deps.storage, &info.sender)?
+        .ok_or(ContractError::Unauthorized {})?;
+    if let Some(allowance) = allowance {
+        let remaining = allowance
+            .checked_sub(amount)
+            .map_err(|_| ContractError::MintAllowanceExceeded {})?;
+        MINTERS.save(deps.storage, &info.sender, &Some(remaining))?;
+    }
+
+    let mut config = TOKEN_INFO.load(deps.storage)?;
+    config.total_supply = config.total_supply.checked_add(amount).map_err(StdError::from)?;
+    if let Some(cap) = CAP.may_load(deps.storage)? {
+        if config.total_supply > cap {
+            return Err(ContractError::CannotExceedCap {});
+        }
+    }
+    TOKEN_INFO.save(deps.storage, &config)?;
+
+    let rcpt_addr = deps.api.addr_validate(&recipient)?;
+    BALANCES.update(deps.storage, &rcpt_addr, |balance| -> StdResult<_> {
+        Ok(balance.unwrap_or_default().checked_add(amount)?)
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "mint")
+        .add_attribute("minter", info.sender)
+        .add_attribute("to", recipient)
+        .add_attribute("amount", amount))
+}
+
+pub fn execute_update_minters(
+    deps: DepsMut,
+    info: MessageInfo,
+    set: Vec<MinterAllowance>,
+    remove: Vec<String>,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.as_ref(), &info.sender)?;
+
+    let mut res = Response::new().add_attribute("action", "update_minters");
+    for MinterAllowance { minter, allowance } in set {
+        let minter = deps.api.addr_validate(&minter)?;
+        MINTERS.save(deps.storage, &minter, &allowance)?;
+        let allowance = allowance.map_or("unlimited".to_string(), |amount| amount.to_string());
+        res = res.add_attribute("set", format!("{}:{}", minter, allowance));
+    }
+    for minter in remove {
+        let minter = deps.api.addr_validate(&minter)?;
+        MINTERS.remove(deps.storage, &minter);
+        res = res.add_attribute("remove", minter);
+    }
+    Ok(res)
+}
+
+pub fn execute_set_paused(
+    deps: DepsMut,
+    info: MessageInfo,
+    paused: bool,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.as_ref(), &info.sender)?;
+    PAUSED.save(deps.storage, &paused)?;
+    Ok(Response::new()
+        .add_attribute("action", "set_paused")
+        .add_attribute("paused", paused.to_string()))
+}
+
+pub fn execute_update_blocklist(
+    deps: DepsMut,
+    info: MessageInfo,
+    add: Vec<String>,
+    remove: Vec<String>,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.as_ref(), &info.sender)?;
+
+    let mut res = Response::new().add_attribute("action", "update_blocklist");
+    for address in add {
+        let address = deps.api.addr_validate(&address)?;
+        BLOCKED.save(deps.storage, &address, &Empty {})?;
+        res = res.add_attribute("block", address);
+    }
+    for address in remove {
+        let address = deps.api.addr_validate(&address)?;
+        BLOCKED.remove(deps.storage, &address);
+        res = res.add_attribute("unblock", address);
+    }
+    Ok(res)
+}
+
+pub fn execute_permit(deps: DepsMut, env: Env, permit: Permit) -> Result<Response, ContractError> {
+    let owner = deps.api.addr_validate(&permit.owner)?;
+    let spender = deps.api.addr_validate(&permit.spender)?;
+    if owner == spender {
+        return Err(cw20_base::ContractError::CannotSetOwnAccount {}.into());
+    }
+    let expires = permit.expires.unwrap_or_default();
+    if expires.is_expired(&env.block) {
+        return Err(ContractError::PermitExpired {});
+    }
+
+    let expected = PERMIT_NONCES.may_load(deps.storage, &owner)?.unwrap_or_default();
+    if permit.nonce != expected {
+        return Err(ContractError::InvalidNonce { expected });
+    }
+    if !owns_address(&owner, &permit.pubkey) {
+        return Err(ContractError::PubkeyMismatch {});
+    }
+
+    let payload = PermitPayload {
+        chain_id: env.block.chain_id,
+        contract: env.contract.address.into(),
+        owner: permit.owner,
+        spender: permit.spender,
+        amount: permit.amount,
+        expires: permit.expires,
+        nonce: permit.nonce,
+    };
+    let hash = Sha256::digest(to_json_vec(&payload)?);
+    let valid = deps
+        .api
+        .secp256k1_verify(&hash, &permit.signature, &permit.pubkey)
+        .map_err(|_| ContractError::InvalidSignature {})?;
+    if !valid {
+        return Err(ContractError::InvalidSignature {});
+    }
+
+    PERMIT_NONCES.save(deps.storage, &owner, &(expected + 1))?;
+    let allowance = AllowanceResponse {
+        allowance: permit.amount,
+        expires,
+    };
+    ALLOWANCES.save(deps.storage, (&owner, &spender), &allowance)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "permit")
+        .add_attribute("owner", owner)
+        .add_attribute("spender", spender)
+        .add_attribute("amount", permit.amount)
+        .add_attribute("nonce", permit.nonce.to_string()))
+}
+
+/// Whether `pubkey` is the compressed secp256k1 key behind the bech32 address
+/// `owner`, which is the RIPEMD-160 of its SHA-256.
+fn owns_address(owner: &Addr, pubkey: &[u8]) -> bool {
+    let (prefix, variant) = match bech32::decode(owner.as_str()) {
+        Ok((prefix, _, variant)) => (prefix, variant),
+        Err(_) => return false,
+    };
+    if pubkey.len() != 33 {
+        return false;
+    }
+    let hash = Ripemd160::digest(Sha256::digest(pubkey));
+    bech32::encode(&prefix, hash.to_base32(), variant)
+        .is_ok_and(|address| address == owner.as_str())
+}
+
+pub fn execute_update_owner(
+    deps: DepsMut,
+    info: MessageInfo,
+    owner: String,
+) -> Result<Response, ContractError> {
+    assert_owner(deps.as_ref(), &info.sender)?;
+    let owner = deps.api.addr_validate(&owner)?;
+    OWNER.save(deps.storage, &owner)?;
+    Ok(Response::new()
+        .add_attribute("action", "update_owner")
+        .add_attribute("owner", owner))
+}
+
+/// Builds the controller notification for a transfer out of `sender`, if this
+/// token has a controller.
+fn controller_hook(
+    deps: Deps,
+    sender: &Addr,
+    recipient: &str,
+    amount: Uint128,
+) -> StdResult<Option<WasmMsg>> {
+    CONTROLLER
+        .may_load(deps.storage)?
+        .map(|controller| {
+            Ok(WasmMsg::Execute {
+                contract_addr: controller.to_string(),
+                msg: to_json_binary(&ControllerHookMsg::ReceiptTransfer {
+                    sender: sender.to_string(),
+                    recipient: recipient.to_string(),
+                    amount,
+                })?,
+                funds: vec![],
+            })
+        })
+        .transpose()
+}
+
+/// Runs the controller hook before anything else the transfer emits, so that a
+/// `Send` receiver never observes a transfer the controller would reject.
+fn prepend_hook(mut res: Response, hook: Option<WasmMsg>) -> Response {
+    if let Some(hook) = hook {
+        res.messages.insert(0, SubMsg::new(hook));
+    }
+    res
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         /* Default methods from CW20 Standard with no modifications:
         https://github.com/CosmWasm/cw-plus/tree/main/contracts/cw20-base */
-        QueryMsg::Balance { address } => to_binary(&query_balance(deps, address)?),
-        QueryMsg::TokenInfo {} => to_binary(&query_token_info(deps)?),
-        QueryMsg::Minter {} => to_binary(&query_minter(deps)?),
+        QueryMsg::Balance { address } => to_json_binary(&query_balance(deps, address)?),
+        QueryMsg::TokenInfo {} => to_json_binary(&query_token_info(deps)?),
+        QueryMsg::Minter {} => to_json_binary(&query_minter(deps)?),
         QueryMsg::Allowance { owner, spender } => {
-            to_binary(&query_allowance(deps, owner, spender)?)
+            to_json_binary(&query_allowance(deps, owner, spender)?)
         }
         QueryMsg::AllAllowances {
             owner,
             start_after,
             limit,
-        } => to_binary(&query_all_allowances(deps, owner, start_after, limit)?),
+        } => to_json_binary(&query_all_allowances(deps, owner, start_after, limit)?),
         QueryMsg::AllAccounts { start_after, limit } => {
-            to_binary(&query_all_accounts(deps, start_after, limit)?)
+            to_json_binary(&query_all_accounts(deps, start_after, limit)?)
         }
-        QueryMsg::MarketingInfo {} => to_binary(&query_marketing_info(deps)?),
-        QueryMsg::DownloadLogo {} => to_binary(&query_download_logo(deps)?),
+        QueryMsg::MarketingInfo {} => to_json_binary(&query_marketing_info(deps)?),
+        QueryMsg::DownloadLogo {} => to_json_binary(&query_download_logo(deps)?),
+        QueryMsg::MinterAllowance { minter } => {
+            to_json_binary(&query_minter_allowance(deps, minter)?)
+        }
+        QueryMsg::AllMinters { start_after, limit } => {
+            to_json_binary(&query_all_minters(deps, start_after, limit)?)
+        }
+        QueryMsg::Compliance {} => to_json_binary(&query_compliance(deps)?),
+        QueryMsg::Blocked { address } => to_json_binary(&query_blocked(deps, address)?),
+        QueryMsg::AllBlocked { start_after, limit } => {
+            to_json_binary(&query_all_blocked(deps, start_after, limit)?)
+        }
+        QueryMsg::BalanceAt { address, height } => {
+            to_json_binary(&query_balance_at(deps, address, height)?)
+        }
+        QueryMsg::TotalSupplyAt { height } => {
+            to_json_binary(&query_total_supply_at(deps, height)?)
+        }
+        QueryMsg::PermitNonce { owner } => to_json_binary(&query_permit_nonce(deps, owner)?),
+        QueryMsg::Vesting { address } => to_json_binary(&query_vesting(deps, env, address)?),
+    }
+}
+
+/// The only minter and the supply cap. `None` unless exactly one account can
+/// mint, since cw20 clients read `minter` as the account that mints.
+pub fn query_minter(deps: Deps) -> StdResult<Option<MinterResponse>> {
+    let minters = MINTERS
+        .keys(deps.storage, None, None, Order::Ascending)
+        .take(2)
+        .collect::<StdResult<Vec<_>>>()?;
+    match minters.as_slice() {
+        [minter] => Ok(Some(MinterResponse {
+            minter: minter.to_string(),
+            cap: CAP.may_load(deps.storage)?,
+        })),
+        _ => Ok(None),
+    }
+}
+
+pub fn query_minter_allowance(deps: Deps, minter: String) -> StdResult<MinterAllowance> {
+    let address = deps.api.addr_validate(&minter)?;
+    let allowance = MINTERS
+        .may_load(deps.storage, &address)?
+        .ok_or_else(|| StdError::not_found("minter"))?;
+    Ok(MinterAllowance { minter, allowance })
+}
+
+pub fn query_all_minters(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<AllMintersResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(|s| Bound::ExclusiveRaw(s.into_bytes()));
+
+    let minters = MINTERS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            item.map(|(minter, allowance)| MinterAllowance {
+                minter: minter.to_string(),
+                allowance,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(AllMintersResponse { minters })
+}
+
+pub fn query_compliance(deps: Deps) -> StdResult<ComplianceResponse> {
+    Ok(ComplianceResponse {
+        owner: OWNER.may_load(deps.storage)?.map(String::from),
+        paused: PAUSED.may_load(deps.storage)?.unwrap_or(false),
+    })
+}
+
+pub fn query_blocked(deps: Deps, address: String) -> StdResult<BlockedResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    Ok(BlockedResponse {
+        blocked: BLOCKED.has(deps.storage, &address),
+    })
+}
+
+pub fn query_all_blocked(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<AllBlockedResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(|s| Bound::ExclusiveRaw(s.into_bytes()));
+
+    let addresses = BLOCKED
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(String::from))
+        .collect::<StdResult<_>>()?;
+    Ok(AllBlockedResponse { addresses })
+}
+
+pub fn query_balance_at(deps: Deps, address: String, height: u64) -> StdResult<BalanceResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let balance = BALANCE_SNAPSHOTS
+        .may_load_at_height(deps.storage, &address, height)?
+        .unwrap_or_default();
+    Ok(BalanceResponse { balance })
+}
+
+pub fn query_permit_nonce(deps: Deps, owner: String) -> StdResult<PermitNonceResponse> {
+    let owner = deps.api.addr_validate(&owner)?;
+    let nonce = PERMIT_NONCES.may_load(deps.storage, &owner)?.unwrap_or_default();
+    Ok(PermitNonceResponse { nonce })
+}
+
+pub fn query_vesting(deps: Deps, env: Env, address: String) -> StdResult<VestingResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let now = env.block.time.seconds();
+    let grants = VESTING.may_load(deps.storage, &address)?.unwrap_or_default();
+    let mut vested = Uint128::zero();
+    let mut locked = Uint128::zero();
+    for grant in &grants {
+        vested = vested.checked_add(grant.vested(now))?;
+        locked = locked.checked_add(grant.locked(now))?;
     }
+    let grants = grants
+        .into_iter()
+        .map(|grant| VestingGrantResponse {
+            amount: grant.amount,
+            schedule: crate::msg::VestingSchedule {
+                start_time: grant.schedule.start_time,
+                cliff_time: grant.schedule.cliff_time,
+                end_time: grant.schedule.end_time,
+            },
+        })
+        .collect();
+    Ok(VestingResponse {
+        vested,
+        locked,
+        grants,
+    })
+}
+
+pub fn query_total_supply_at(deps: Deps, height: u64) -> StdResult<TotalSupplyResponse> {
+    let total_supply = SUPPLY_SNAPSHOTS
+        .may_load_at_height(deps.storage, height)?
+        .unwrap_or_default();
+    Ok(TotalSupplyResponse { total_supply })
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(_deps: DepsMut, _env: Env, _msg: MigrateMsg) -> StdResult<Response> {
+pub fn migrate(deps: DepsMut, env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    // Tokens instantiated before minter allowances keep their cw20-base minter,
+    // which also becomes the owner.
+    if OWNER.may_load(deps.storage)?.is_none() {
+        if let Some(mint) = TOKEN_INFO.load(deps.storage)?.mint {
+            OWNER.save(deps.storage, &mint.minter)?;
+            MINTERS.save(deps.storage, &mint.minter, &None)?;
+        }
+    }
+    // The cap used to be read from cw20-base's minter data.
+    if CAP.may_load(deps.storage)?.is_none() {
+        if let Some(cap) = TOKEN_INFO.load(deps.storage)?.get_cap() {
+            CAP.save(deps.storage, &cap)?;
+        }
+    }
+    // Snapshots start from the current balances; earlier heights read as zero.
+    if SUPPLY_SNAPSHOTS.may_load(deps.storage)?.is_none() {
+        let holders = BALANCES
+            .keys(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        snapshot_balances(deps.storage, env.block.height, &holders)?;
+        snapshot_supply(deps.storage, env.block.height)?;
+    }
     Ok(Response::default())
 }
\ No newline at end of file
@@ -0,0 +1,50 @@
+use cosmwasm_std::{StdError, Uint128};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ContractError {
+    #[error("{0}")]
+    Std(#[from] StdError),
+
+    #[error("{0}")]
+    Base(#[from] cw20_base::ContractError),
+
+    #[error("Unauthorized")]
+    Unauthorized {},
+
+    #[error("Invalid zero amount")]
+    InvalidZeroAmount {},
+
+    #[error("Minting cannot exceed the cap")]
+    CannotExceedCap {},
+
+    #[error("Mint exceeds the minter's allowance")]
+    MintAllowanceExceeded {},
+
+    #[error("Only the controller can burn this token")]
+    ControlledBurn {},
+
+    #[error("Transfers are paused")]
+    Paused {},
+
+    #[error("Address {address} is blocked")]
+    Blocked { address: String },
+
+    #[error("Permit has expired")]
+    PermitExpired {},
+
+    #[error("Invalid permit nonce, expected {expected}")]
+    InvalidNonce { expected: u64 },
+
+    #[error("Public key does not belong to the permit owner")]
+    PubkeyMismatch {},
+
+    #[error("Invalid permit signature")]
+    InvalidSignature {},
+
+    #[error("Vesting must start no later than the cliff, and the cliff no later than the end")]
+    InvalidSchedule {},
+
+    #[error("Balance would fall below the {locked} tokens still vesting")]
+    TokensLocked { locked: Uint128 },
+}
@@ -1,2 +1,7 @@
 pub mod contract;
-pub mod msg;
\ No newline at end of file
+pub mod error;
+pub mod msg;
+pub mod state;
+
+#[cfg(test)]
+mod tests;
@@ -1,5 +1,279 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use cosmwasm_std::{Binary, Uint128};
+use cw20::{Expiration, Logo};
 
+/// The cw20-base instantiate message, plus an optional controller contract.
+/// `base.mint.minter` becomes the first minter, with no allowance limit, and
+/// `base.mint.cap` is a hard cap on total supply across all minters. The
+/// instantiating address becomes the owner.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-pub struct MigrateMsg {}
\ No newline at end of file
+pub struct InstantiateMsg {
+    #[serde(flatten)]
+    pub base: cw20_base::msg::InstantiateMsg,
+    /// Contract that is notified of, and may reject, every transfer. It can
+    /// also burn from any account without an allowance, and is the only
+    /// account that can burn at all.
+    pub controller: Option<String>,
+}
+
+/// The cw20 execute messages, plus minter management.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    Transfer {
+        recipient: String,
+        amount: Uint128,
+    },
+    Burn {
+        amount: Uint128,
+    },
+    Send {
+        contract: String,
+        amount: Uint128,
+        msg: Binary,
+    },
+    IncreaseAllowance {
+        spender: String,
+        amount: Uint128,
+        expires: Option<Expiration>,
+    },
+    DecreaseAllowance {
+        spender: String,
+        amount: Uint128,
+        expires: Option<Expiration>,
+    },
+    TransferFrom {
+        owner: String,
+        recipient: String,
+        amount: Uint128,
+    },
+    SendFrom {
+        owner: String,
+        contract: String,
+        amount: Uint128,
+        msg: Binary,
+    },
+    BurnFrom {
+        owner: String,
+        amount: Uint128,
+    },
+    /// Any minter may mint up to its remaining allowance, within the supply
+    /// cap.
+    Mint {
+        recipient: String,
+        amount: Uint128,
+    },
+    /// Mints like `Mint`, but `amount` stays locked in the recipient's balance
+    /// and releases on `schedule`. Locked tokens cannot be transferred, sent
+    /// or burned.
+    MintVested {
+        recipient: String,
+        amount: Uint128,
+        schedule: VestingSchedule,
+    },
+    UpdateMarketing {
+        project: Option<String>,
+        description: Option<String>,
+        marketing: Option<String>,
+    },
+    UploadLogo(Logo),
+    /// Owner only. Adds the `set` minters or replaces their allowance, then
+    /// removes the `remove` minters.
+    UpdateMinters {
+        set: Vec<MinterAllowance>,
+        remove: Vec<String>,
+    },
+    /// Owner only. Stops or resumes all transfers, sends and mints. Burns are
+    /// unaffected.
+    SetPaused {
+        paused: bool,
+    },
+    /// Owner only. Blocked addresses can neither send, receive nor mint.
+    UpdateBlocklist {
+        add: Vec<String>,
+        remove: Vec<String>,
+    },
+    /// Owner only. Hands the owner role to `owner`.
+    UpdateOwner {
+        owner: String,
+    },
+    /// Sets `spender`'s allowance from `owner` with a signature instead of a
+    /// transaction from `owner`. Anyone may submit it.
+    Permit(Permit),
+}
+
+/// An allowance signed off-chain. `signature` is a 64-byte secp256k1
+/// signature by `pubkey` over the SHA-256 of the JSON `PermitPayload`, and
+/// `pubkey` is the 33-byte compressed key behind the `owner` address.
+/// `nonce` must equal the owner's `PermitNonce`. The allowance expires at
+/// `expires`, after which the permit can no longer be used.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Permit {
+    pub owner: String,
+    pub spender: String,
+    pub amount: Uint128,
+    pub expires: Option<Expiration>,
+    pub nonce: u64,
+    pub signature: Binary,
+    pub pubkey: Binary,
+}
+
+/// The document signed for a `Permit`. Binding it to the chain and token
+/// contract keeps a signature from being replayed elsewhere.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PermitPayload {
+    pub chain_id: String,
+    pub contract: String,
+    pub owner: String,
+    pub spender: String,
+    pub amount: Uint128,
+    pub expires: Option<Expiration>,
+    pub nonce: u64,
+}
+
+/// The cw20 queries, plus minter allowances.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    Balance {
+        address: String,
+    },
+    TokenInfo {},
+    /// Return type: `Option<cw20::MinterResponse>`. The minter and the
+    /// supply cap while exactly one account can mint, otherwise `None`. Use
+    /// `AllMinters` for every account that can mint, and `Compliance` for the
+    /// owner who manages them.
+    Minter {},
+    Allowance {
+        owner: String,
+        spender: String,
+    },
+    AllAllowances {
+        owner: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    AllAccounts {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    MarketingInfo {},
+    DownloadLogo {},
+    /// Return type: `MinterAllowance`. Fails for addresses that are not
+    /// minters.
+    MinterAllowance {
+        minter: String,
+    },
+    /// Return type: `AllMintersResponse`.
+    AllMinters {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Return type: `ComplianceResponse`.
+    Compliance {},
+    /// Return type: `BlockedResponse`.
+    Blocked {
+        address: String,
+    },
+    /// Return type: `AllBlockedResponse`.
+    AllBlocked {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Balance at the start of block `height`, before any of its transactions.
+    /// Return type: `cw20::BalanceResponse`.
+    BalanceAt {
+        address: String,
+        height: u64,
+    },
+    /// Total supply at the start of block `height`.
+    /// Return type: `TotalSupplyResponse`.
+    TotalSupplyAt {
+        height: u64,
+    },
+    /// Return type: `PermitNonceResponse`.
+    PermitNonce {
+        owner: String,
+    },
+    /// Return type: `VestingResponse`.
+    Vesting {
+        address: String,
+    },
+}
+
+/// A minter and how much it may still mint. `None` is unlimited.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MinterAllowance {
+    pub minter: String,
+    pub allowance: Option<Uint128>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AllMintersResponse {
+    pub minters: Vec<MinterAllowance>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ComplianceResponse {
+    pub owner: Option<String>,
+    pub paused: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BlockedResponse {
+    pub blocked: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AllBlockedResponse {
+    pub addresses: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TotalSupplyResponse {
+    pub total_supply: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PermitNonceResponse {
+    pub nonce: u64,
+}
+
+/// Totals across an address's vested mints at the current block.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VestingResponse {
+    pub vested: Uint128,
+    pub locked: Uint128,
+    pub grants: Vec<VestingGrantResponse>,
+}
+
+/// Times in seconds. Nothing releases before `cliff_time`; from then on the
+/// grant has released its share of the time from `start_time` to `end_time`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VestingSchedule {
+    pub start_time: u64,
+    pub cliff_time: u64,
+    pub end_time: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VestingGrantResponse {
+    pub amount: Uint128,
+    pub schedule: VestingSchedule,
+}
+
+/// Sent to the controller ahead of any other message produced by a transfer.
+/// If the controller returns an error, the whole transfer is reverted.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ControllerHookMsg {
+    ReceiptTransfer {
+        sender: String,
+        recipient: String,
+        amount: Uint128,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateMsg {}
@@ -0,0 +1,92 @@
+use cosmwasm_std::{Addr, Empty, Uint128};
+use cw_storage_plus::{Item, Map, SnapshotItem, SnapshotMap, Strategy};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Release schedule of a vested mint, in seconds since the epoch. Tokens
+/// release linearly from `start_time` to `end_time`, but none before
+/// `cliff_time`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VestingSchedule {
+    pub start_time: u64,
+    pub cliff_time: u64,
+    pub end_time: u64,
+}
+
+impl VestingSchedule {
+    pub fn is_valid(&self) -> bool {
+        self.start_time <= self.cliff_time
+            && self.cliff_time <= self.end_time
+            && self.start_time < self.end_time
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VestingGrant {
+    pub amount: Uint128,
+    pub schedule: VestingSchedule,
+}
+
+impl VestingGrant {
+    /// Part of `amount` released at `now`, rounded down.
+    pub fn vested(&self, now: u64) -> Uint128 {
+        let schedule = &self.schedule;
+        if now < schedule.cliff_time {
+            Uint128::zero()
+        } else if now >= schedule.end_time {
+            self.amount
+        } else {
+            self.amount.multiply_ratio(
+                now - schedule.start_time,
+                schedule.end_time - schedule.start_time,
+            )
+        }
+    }
+
+    pub fn locked(&self, now: u64) -> Uint128 {
+        self.amount - self.vested(now)
+    }
+}
+
+pub const CONTROLLER: Item<Addr> = Item::new("controller");
+
+/// May add, update and remove minters, pause transfers and maintain the
+/// blocklist.
+pub const OWNER: Item<Addr> = Item::new("owner");
+
+/// Amount each minter may still mint. `None` is unlimited.
+pub const MINTERS: Map<&Addr, Option<Uint128>> = Map::new("minters");
+
+/// Hard cap on total supply across all minters, from `base.mint.cap`. Unset
+/// means uncapped.
+pub const CAP: Item<Uint128> = Item::new("cap");
+
+/// While set, no tokens can be transferred, sent or minted.
+pub const PAUSED: Item<bool> = Item::new("paused");
+
+/// Addresses that may not send, receive or mint tokens.
+pub const BLOCKED: Map<&Addr, Empty> = Map::new("blocked");
+
+/// Vested mints per holder. Fully released grants are dropped on the holder's
+/// next vested mint.
+pub const VESTING: Map<&Addr, Vec<VestingGrant>> = Map::new("vesting");
+
+/// Nonce the next permit signed by each owner must carry.
+pub const PERMIT_NONCES: Map<&Addr, u64> = Map::new("permit_nonces");
+
+/// Copy of cw20-base's `BALANCES`, updated after every change, that keeps each
+/// account's balance by block height.
+pub const BALANCE_SNAPSHOTS: SnapshotMap<&Addr, Uint128> = SnapshotMap::new(
+    "balance_snapshots",
+    "balance_snapshots__checkpoints",
+    "balance_snapshots__changelog",
+    Strategy::EveryBlock,
+);
+
+/// Total supply by block height, as `BALANCE_SNAPSHOTS` for `TOKEN_INFO`.
+pub const SUPPLY_SNAPSHOTS: SnapshotItem<Uint128> = SnapshotItem::new(
+    "supply_snapshots",
+    "supply_snapshots__checkpoints",
+    "supply_snapshots__changelog",
+    Strategy::EveryBlock,
+);
@@ -0,0 +1,689 @@
+use bech32::{ToBase32, Variant};
+use cosmwasm_std::testing::{
+    mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage,
+};
+use cosmwasm_std::{from_json, to_json_vec, Addr, Binary, Env, OwnedDeps, StdResult, Uint128};
+use cw20::{AllowanceResponse, Expiration};
+use cw20::{BalanceResponse, MinterResponse, TokenInfoResponse};
+use cw20_base::state::TOKEN_INFO;
+use k256::ecdsa::signature::hazmat::PrehashSigner;
+use k256::ecdsa::{Signature, SigningKey};
+use ripemd::Ripemd160;
+use sha2::{Digest, Sha256};
+
+use crate::contract::{execute, instantiate, migrate, query};
+use crate::error::ContractError;
+use crate::msg::{
+    AllBlockedResponse, AllMintersResponse, ComplianceResponse, ExecuteMsg, InstantiateMsg,
+    MigrateMsg, MinterAllowance, Permit, PermitNonceResponse, PermitPayload, QueryMsg,
+    TotalSupplyResponse, VestingGrantResponse, VestingResponse, VestingSchedule,
+};
+use crate::state::{BALANCE_SNAPSHOTS, CONTROLLER, MINTERS, OWNER, SUPPLY_SNAPSHOTS};
+
+const OWNER_ADDR: &str = "owner";
+const MINTER: &str = "minter";
+const LENDING: &str = "lending";
+const ALICE: &str = "alice";
+const CAP: u128 = 1_000;
+
+type Deps = OwnedDeps<MockStorage, MockApi, MockQuerier>;
+
+/// Instantiates the token with `MINTER` capped at `CAP`. With `vesting`,
+/// also mints `ALICE` 100 unlocked tokens and 800 vesting over the next 100
+/// seconds, with a cliff at 25.
+fn setup(vesting: bool) -> Deps {
+    let mut deps = mock_dependencies();
+    let msg = InstantiateMsg {
+        base: cw20_base::msg::InstantiateMsg {
+            name: "USD".to_string(),
+            symbol: "USD".to_string(),
+            decimals: 6,
+            initial_balances: vec![],
+            mint: Some(MinterResponse {
+                minter: MINTER.to_string(),
+                cap: Some(Uint128::new(CAP)),
+            }),
+            marketing: None,
+        },
+        controller: None,
+    };
+    instantiate(deps.as_mut(), mock_env(), mock_info(OWNER_ADDR, &[]), msg).unwrap();
+    if vesting {
+        mint(&mut deps, MINTER, 100).unwrap();
+        let start = mock_env().block.time.seconds();
+        let msg = ExecuteMsg::MintVested {
+            recipient: ALICE.to_string(),
+            amount: Uint128::new(800),
+            schedule: VestingSchedule {
+                start_time: start,
+                cliff_time: start + 25,
+                end_time: start + 100,
+            },
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(MINTER, &[]), msg).unwrap();
+    }
+    deps
+}
+
+fn mint(deps: &mut Deps, sender: &str, amount: u128) -> Result<(), ContractError> {
+    let msg = ExecuteMsg::Mint {
+        recipient: ALICE.to_string(),
+        amount: Uint128::new(amount),
+    };
+    execute(deps.as_mut(), mock_env(), mock_info(sender, &[]), msg).map(|_| ())
+}
+
+fn set_minter(
+    deps: &mut Deps,
+    sender: &str,
+    minter: &str,
+    allowance: Option<u128>,
+) -> Result<(), ContractError> {
+    let msg = ExecuteMsg::UpdateMinters {
+        set: vec![MinterAllowance {
+            minter: minter.to_string(),
+            allowance: allowance.map(Uint128::new),
+        }],
+        remove: vec![],
+    };
+    execute(deps.as_mut(), mock_env(), mock_info(sender, &[]), msg).map(|_| ())
+}
+
+fn allowance(deps: &Deps, minter: &str) -> Option<Uint128> {
+    let msg = QueryMsg::MinterAllowance {
+        minter: minter.to_string(),
+    };
+    let res: MinterAllowance = from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    res.allowance
+}
+
+fn balance(deps: &Deps, address: &str) -> Uint128 {
+    let msg = QueryMsg::Balance {
+        address: address.to_string(),
+    };
+    let res: BalanceResponse = from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    res.balance
+}
+
+#[test]
+fn initial_minter_is_unlimited_up_to_cap() {
+    let mut deps = setup(false);
+    assert_eq!(allowance(&deps, MINTER), None);
+
+    mint(&mut deps, MINTER, 600).unwrap();
+    assert_eq!(balance(&deps, ALICE), Uint128::new(600));
+
+    let err = mint(&mut deps, MINTER, 401).unwrap_err();
+    assert_eq!(err, ContractError::CannotExceedCap {});
+    mint(&mut deps, MINTER, 400).unwrap();
+
+    let res: TokenInfoResponse =
+        from_json(query(deps.as_ref(), mock_env(), QueryMsg::TokenInfo {}).unwrap()).unwrap();
+    assert_eq!(res.total_supply, Uint128::new(CAP));
+}
+
+#[test]
+fn minter_allowance_is_spent() {
+    let mut deps = setup(false);
+    set_minter(&mut deps, OWNER_ADDR, LENDING, Some(100)).unwrap();
+
+    mint(&mut deps, LENDING, 70).unwrap();
+    assert_eq!(allowance(&deps, LENDING), Some(Uint128::new(30)));
+
+    let err = mint(&mut deps, LENDING, 31).unwrap_err();
+    assert_eq!(err, ContractError::MintAllowanceExceeded {});
+    mint(&mut deps, LENDING, 30).unwrap();
+    assert_eq!(allowance(&deps, LENDING), Some(Uint128::zero()));
+}
+
+#[test]
+fn mint_errors() {
+    let mut deps = setup(false);
+    let err = mint(&mut deps, ALICE, 1).unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    let err = mint(&mut deps, MINTER, 0).unwrap_err();
+    assert_eq!(err, ContractError::InvalidZeroAmount {});
+    // Non-minters have no allowance to report.
+    let msg = QueryMsg::MinterAllowance {
+        minter: ALICE.to_string(),
+    };
+    query(deps.as_ref(), mock_env(), msg).unwrap_err();
+}
+
+#[test]
+fn update_minters() {
+    let mut deps = setup(false);
+    let err = set_minter(&mut deps, MINTER, ALICE, None).unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    set_minter(&mut deps, OWNER_ADDR, LENDING, Some(5)).unwrap();
+    let msg = ExecuteMsg::UpdateMinters {
+        set: vec![],
+        remove: vec![MINTER.to_string()],
+    };
+    execute(deps.as_mut(), mock_env(), mock_info(OWNER_ADDR, &[]), msg).unwrap();
+    let err = mint(&mut deps, MINTER, 1).unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    let msg = QueryMsg::AllMinters {
+        start_after: None,
+        limit: None,
+    };
+    let res: AllMintersResponse =
+        from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    assert_eq!(
+        res.minters,
+        vec![MinterAllowance {
+            minter: LENDING.to_string(),
+            allowance: Some(Uint128::new(5)),
+        }]
+    );
+}
+
+#[test]
+fn minter_query_reports_sole_minter_and_cap() {
+    let mut deps = setup(false);
+    let minter = |deps: &Deps| -> Option<MinterResponse> {
+        from_json(query(deps.as_ref(), mock_env(), QueryMsg::Minter {}).unwrap()).unwrap()
+    };
+    let expected = |minter: &str| MinterResponse {
+        minter: minter.to_string(),
+        cap: Some(Uint128::new(CAP)),
+    };
+    assert_eq!(minter(&deps), Some(expected(MINTER)));
+    set_minter(&mut deps, OWNER_ADDR, LENDING, Some(5)).unwrap();
+    assert_eq!(minter(&deps), None);
+
+    // The cap holds even without cw20-base's minter data.
+    TOKEN_INFO
+        .update(deps.as_mut().storage, |mut info| -> StdResult<_> {
+            info.mint = None;
+            Ok(info)
+        })
+        .unwrap();
+    let err = mint(&mut deps, MINTER, CAP + 1).unwrap_err();
+    assert_eq!(err, ContractError::CannotExceedCap {});
+
+    let remove = |deps: &mut Deps, minter: &str| {
+        let msg = ExecuteMsg::UpdateMinters {
+            set: vec![],
+            remove: vec![minter.to_string()],
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(OWNER_ADDR, &[]), msg).unwrap();
+    };
+    remove(&mut deps, MINTER);
+    assert_eq!(minter(&deps), Some(expected(LENDING)));
+    remove(&mut deps, LENDING);
+    assert_eq!(minter(&deps), None);
+}
+
+#[test]
+fn migrate_backfills_minter() {
+    let mut deps = setup(false);
+    OWNER.remove(deps.as_mut().storage);
+    MINTERS.remove(deps.as_mut().storage, &Addr::unchecked(MINTER));
+
+    crate::state::CAP.remove(deps.as_mut().storage);
+
+    migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+    assert_eq!(allowance(&deps, MINTER), None);
+    set_minter(&mut deps, MINTER, LENDING, Some(1)).unwrap();
+    let err = mint(&mut deps, MINTER, CAP + 1).unwrap_err();
+    assert_eq!(err, ContractError::CannotExceedCap {});
+}
+
+fn transfer(
+    deps: &mut Deps,
+    env: Env,
+    sender: &str,
+    recipient: &str,
+    amount: u128,
+) -> Result<(), ContractError> {
+    let msg = ExecuteMsg::Transfer {
+        recipient: recipient.to_string(),
+        amount: Uint128::new(amount),
+    };
+    execute(deps.as_mut(), env, mock_info(sender, &[]), msg).map(|_| ())
+}
+
+fn block(deps: &mut Deps, address: &str) -> Result<(), ContractError> {
+    let msg = ExecuteMsg::UpdateBlocklist {
+        add: vec![address.to_string()],
+        remove: vec![],
+    };
+    execute(deps.as_mut(), mock_env(), mock_info(OWNER_ADDR, &[]), msg).map(|_| ())
+}
+
+#[test]
+fn pause_stops_transfers_and_mints() {
+    let mut deps = setup(false);
+    mint(&mut deps, MINTER, 100).unwrap();
+
+    let msg = ExecuteMsg::SetPaused { paused: true };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(ALICE, &[]),
+        msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    execute(deps.as_mut(), mock_env(), mock_info(OWNER_ADDR, &[]), msg).unwrap();
+
+    assert_eq!(
+        transfer(&mut deps, mock_env(), ALICE, LENDING, 1).unwrap_err(),
+        ContractError::Paused {}
+    );
+    assert_eq!(
+        mint(&mut deps, MINTER, 1).unwrap_err(),
+        ContractError::Paused {}
+    );
+    // Burns still go through.
+    let msg = ExecuteMsg::Burn {
+        amount: Uint128::new(1),
+    };
+    execute(deps.as_mut(), mock_env(), mock_info(ALICE, &[]), msg).unwrap();
+
+    let res: ComplianceResponse =
+        from_json(query(deps.as_ref(), mock_env(), QueryMsg::Compliance {}).unwrap()).unwrap();
+    assert_eq!(
+        res,
+        ComplianceResponse {
+            owner: Some(OWNER_ADDR.to_string()),
+            paused: true,
+        }
+    );
+
+    let msg = ExecuteMsg::SetPaused { paused: false };
+    execute(deps.as_mut(), mock_env(), mock_info(OWNER_ADDR, &[]), msg).unwrap();
+    transfer(&mut deps, mock_env(), ALICE, LENDING, 1).unwrap();
+}
+
+#[test]
+fn controller_is_the_only_burner() {
+    let mut deps = setup(false);
+    mint(&mut deps, MINTER, 100).unwrap();
+    CONTROLLER
+        .save(deps.as_mut().storage, &Addr::unchecked(LENDING))
+        .unwrap();
+
+    let burn = ExecuteMsg::Burn {
+        amount: Uint128::new(1),
+    };
+    let err = execute(deps.as_mut(), mock_env(), mock_info(ALICE, &[]), burn).unwrap_err();
+    assert_eq!(err, ContractError::ControlledBurn {});
+
+    let msg = ExecuteMsg::IncreaseAllowance {
+        spender: MINTER.to_string(),
+        amount: Uint128::new(100),
+        expires: None,
+    };
+    execute(deps.as_mut(), mock_env(), mock_info(ALICE, &[]), msg).unwrap();
+    let burn_from = ExecuteMsg::BurnFrom {
+        owner: ALICE.to_string(),
+        amount: Uint128::new(10),
+    };
+    let err = execute(deps.as_mut(), mock_env(), mock_info(MINTER, &[]), burn_from.clone())
+        .unwrap_err();
+    assert_eq!(err, ContractError::ControlledBurn {});
+
+    execute(deps.as_mut(), mock_env(), mock_info(LENDING, &[]), burn_from).unwrap();
+    assert_eq!(balance(&deps, ALICE), Uint128::new(90));
+}
+
+#[test]
+fn blocked_addresses() {
+    let mut deps = setup(false);
+    mint(&mut deps, MINTER, 100).unwrap();
+    block(&mut deps, LENDING).unwrap();
+
+    let blocked = ContractError::Blocked {
+        address: LENDING.to_string(),
+    };
+    assert_eq!(transfer(&mut deps, mock_env(), ALICE, LENDING, 1).unwrap_err(), blocked);
+    let msg = ExecuteMsg::Mint {
+        recipient: LENDING.to_string(),
+        amount: Uint128::new(1),
+    };
+    let err = execute(deps.as_mut(), mock_env(), mock_info(MINTER, &[]), msg).unwrap_err();
+    assert_eq!(err, blocked);
+
+    // A blocked spender cannot move an owner's tokens either.
+    let msg = ExecuteMsg::IncreaseAllowance {
+        spender: LENDING.to_string(),
+        amount: Uint128::new(10),
+        expires: None,
+    };
+    execute(deps.as_mut(), mock_env(), mock_info(ALICE, &[]), msg).unwrap();
+    let msg = ExecuteMsg::TransferFrom {
+        owner: ALICE.to_string(),
+        recipient: MINTER.to_string(),
+        amount: Uint128::new(1),
+    };
+    let err = execute(deps.as_mut(), mock_env(), mock_info(LENDING, &[]), msg).unwrap_err();
+    assert_eq!(err, blocked);
+
+    let msg = QueryMsg::AllBlocked {
+        start_after: None,
+        limit: None,
+    };
+    let res: AllBlockedResponse =
+        from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    assert_eq!(res.addresses, vec![LENDING.to_string()]);
+
+    let msg = ExecuteMsg::UpdateBlocklist {
+        add: vec![],
+        remove: vec![LENDING.to_string()],
+    };
+    execute(deps.as_mut(), mock_env(), mock_info(OWNER_ADDR, &[]), msg).unwrap();
+    transfer(&mut deps, mock_env(), ALICE, LENDING, 1).unwrap();
+}
+
+#[test]
+fn update_owner() {
+    let mut deps = setup(false);
+    let msg = ExecuteMsg::UpdateOwner {
+        owner: ALICE.to_string(),
+    };
+    execute(deps.as_mut(), mock_env(), mock_info(OWNER_ADDR, &[]), msg).unwrap();
+    assert_eq!(
+        block(&mut deps, LENDING).unwrap_err(),
+        ContractError::Unauthorized {}
+    );
+    set_minter(&mut deps, ALICE, LENDING, None).unwrap();
+}
+
+/// `mock_env()` at block `height` and `seconds` past the mock block time.
+fn env_at(height: Option<u64>, seconds: Option<u64>) -> Env {
+    let mut env = mock_env();
+    if let Some(height) = height {
+        env.block.height = height;
+    }
+    if let Some(seconds) = seconds {
+        env.block.time = env.block.time.plus_seconds(seconds);
+    }
+    env
+}
+
+fn balance_at(deps: &Deps, address: &str, height: u64) -> u128 {
+    let msg = QueryMsg::BalanceAt {
+        address: address.to_string(),
+        height,
+    };
+    let res: BalanceResponse = from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    res.balance.u128()
+}
+
+fn total_supply_at(deps: &Deps, height: u64) -> u128 {
+    let msg = QueryMsg::TotalSupplyAt { height };
+    let res: TotalSupplyResponse =
+        from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    res.total_supply.u128()
+}
+
+#[test]
+fn balances_by_height() {
+    let mut deps = setup(false);
+    let start = mock_env().block.height;
+    let mint = ExecuteMsg::Mint {
+        recipient: ALICE.to_string(),
+        amount: Uint128::new(100),
+    };
+    execute(
+        deps.as_mut(),
+        env_at(Some(start + 1), None),
+        mock_info(MINTER, &[]),
+        mint,
+    )
+    .unwrap();
+    let transfer = ExecuteMsg::Transfer {
+        recipient: LENDING.to_string(),
+        amount: Uint128::new(30),
+    };
+    execute(
+        deps.as_mut(),
+        env_at(Some(start + 3), None),
+        mock_info(ALICE, &[]),
+        transfer,
+    )
+    .unwrap();
+    let burn = ExecuteMsg::Burn {
+        amount: Uint128::new(10),
+    };
+    execute(
+        deps.as_mut(),
+        env_at(Some(start + 5), None),
+        mock_info(LENDING, &[]),
+        burn,
+    )
+    .unwrap();
+
+    // Values apply from the block after the change.
+    assert_eq!(balance_at(&deps, ALICE, start + 1), 0);
+    assert_eq!(balance_at(&deps, ALICE, start + 2), 100);
+    assert_eq!(balance_at(&deps, ALICE, start + 4), 70);
+    assert_eq!(balance_at(&deps, LENDING, start + 3), 0);
+    assert_eq!(balance_at(&deps, LENDING, start + 4), 30);
+    assert_eq!(balance_at(&deps, LENDING, start + 6), 20);
+
+    assert_eq!(total_supply_at(&deps, start + 1), 0);
+    assert_eq!(total_supply_at(&deps, start + 2), 100);
+    assert_eq!(total_supply_at(&deps, start + 5), 100);
+    assert_eq!(total_supply_at(&deps, start + 6), 90);
+}
+
+#[test]
+fn migrate_seeds_snapshots() {
+    let mut deps = setup(false);
+    mint(&mut deps, MINTER, 100).unwrap();
+    let height = mock_env().block.height;
+    BALANCE_SNAPSHOTS
+        .remove(deps.as_mut().storage, &Addr::unchecked(ALICE), height)
+        .unwrap();
+    SUPPLY_SNAPSHOTS
+        .remove(deps.as_mut().storage, height)
+        .unwrap();
+
+    migrate(deps.as_mut(), env_at(Some(height + 10), None), MigrateMsg {}).unwrap();
+    assert_eq!(balance_at(&deps, ALICE, height + 10), 0);
+    assert_eq!(balance_at(&deps, ALICE, height + 11), 100);
+    assert_eq!(total_supply_at(&deps, height + 11), 100);
+}
+
+/// A key and the bech32 address it controls.
+fn signer(seed: u8) -> (SigningKey, String) {
+    let key = SigningKey::from_slice(&[seed; 32]).unwrap();
+    let pubkey = key.verifying_key().to_encoded_point(true);
+    let hash = Ripemd160::digest(Sha256::digest(pubkey.as_bytes()));
+    let address = bech32::encode("mantra", hash.to_base32(), Variant::Bech32).unwrap();
+    (key, address)
+}
+
+fn sign_permit(key: &SigningKey, owner: &str, amount: u128, nonce: u64) -> Permit {
+    let env = mock_env();
+    let payload = PermitPayload {
+        chain_id: env.block.chain_id,
+        contract: env.contract.address.into(),
+        owner: owner.to_string(),
+        spender: LENDING.to_string(),
+        amount: Uint128::new(amount),
+        expires: Some(Expiration::AtHeight(env.block.height + 100)),
+        nonce,
+    };
+    let hash = Sha256::digest(to_json_vec(&payload).unwrap());
+    let signature: Signature = key.sign_prehash(&hash).unwrap();
+    Permit {
+        owner: payload.owner,
+        spender: payload.spender,
+        amount: payload.amount,
+        expires: payload.expires,
+        nonce,
+        signature: Binary::from(signature.to_bytes().as_slice()),
+        pubkey: Binary::from(key.verifying_key().to_encoded_point(true).as_bytes()),
+    }
+}
+
+fn permit(deps: &mut Deps, permit: Permit) -> Result<(), ContractError> {
+    let msg = ExecuteMsg::Permit(permit);
+    execute(deps.as_mut(), mock_env(), mock_info(ALICE, &[]), msg).map(|_| ())
+}
+
+#[test]
+fn permit_sets_allowance() {
+    let mut deps = setup(false);
+    let (key, owner) = signer(1);
+    let msg = ExecuteMsg::Mint {
+        recipient: owner.clone(),
+        amount: Uint128::new(100),
+    };
+    execute(deps.as_mut(), mock_env(), mock_info(MINTER, &[]), msg).unwrap();
+
+    permit(&mut deps, sign_permit(&key, &owner, 60, 0)).unwrap();
+    let msg = QueryMsg::Allowance {
+        owner: owner.clone(),
+        spender: LENDING.to_string(),
+    };
+    let res: AllowanceResponse = from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    assert_eq!(res.allowance, Uint128::new(60));
+    let msg = QueryMsg::PermitNonce {
+        owner: owner.clone(),
+    };
+    let res: PermitNonceResponse =
+        from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    assert_eq!(res.nonce, 1);
+
+    let msg = ExecuteMsg::TransferFrom {
+        owner: owner.clone(),
+        recipient: LENDING.to_string(),
+        amount: Uint128::new(60),
+    };
+    execute(deps.as_mut(), mock_env(), mock_info(LENDING, &[]), msg).unwrap();
+    assert_eq!(balance(&deps, LENDING), Uint128::new(60));
+
+    // A permit sets the allowance rather than adding to it.
+    permit(&mut deps, sign_permit(&key, &owner, 10, 1)).unwrap();
+    permit(&mut deps, sign_permit(&key, &owner, 20, 2)).unwrap();
+    let msg = QueryMsg::Allowance {
+        owner,
+        spender: LENDING.to_string(),
+    };
+    let res: AllowanceResponse = from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    assert_eq!(res.allowance, Uint128::new(20));
+}
+
+#[test]
+fn permit_errors() {
+    let mut deps = setup(false);
+    let (key, owner) = signer(1);
+    let signed = sign_permit(&key, &owner, 60, 0);
+    permit(&mut deps, signed.clone()).unwrap();
+
+    let err = permit(&mut deps, signed.clone()).unwrap_err();
+    assert_eq!(err, ContractError::InvalidNonce { expected: 1 });
+
+    let mut tampered = sign_permit(&key, &owner, 60, 1);
+    tampered.amount = Uint128::new(61);
+    assert_eq!(
+        permit(&mut deps, tampered).unwrap_err(),
+        ContractError::InvalidSignature {}
+    );
+
+    // Signed by a key that does not control the owner address.
+    let (other, _) = signer(2);
+    let err = permit(&mut deps, sign_permit(&other, &owner, 60, 1)).unwrap_err();
+    assert_eq!(err, ContractError::PubkeyMismatch {});
+
+    let mut expired = sign_permit(&key, &owner, 60, 1);
+    expired.expires = Some(Expiration::AtHeight(1));
+    assert_eq!(
+        permit(&mut deps, expired).unwrap_err(),
+        ContractError::PermitExpired {}
+    );
+}
+
+fn vesting_at(deps: &Deps, seconds: u64) -> VestingResponse {
+    let msg = QueryMsg::Vesting {
+        address: ALICE.to_string(),
+    };
+    from_json(query(deps.as_ref(), env_at(None, Some(seconds)), msg).unwrap()).unwrap()
+}
+
+#[test]
+fn vesting_releases_after_cliff() {
+    let mut deps = setup(true);
+    assert_eq!(balance(&deps, ALICE), Uint128::new(900));
+
+    let start = mock_env().block.time.seconds();
+    assert_eq!(
+        vesting_at(&deps, 0).grants,
+        vec![VestingGrantResponse {
+            amount: Uint128::new(800),
+            schedule: VestingSchedule {
+                start_time: start,
+                cliff_time: start + 25,
+                end_time: start + 100,
+            },
+        }]
+    );
+
+    // Before the cliff only the unlocked mint can move.
+    let res = vesting_at(&deps, 24);
+    assert_eq!((res.vested.u128(), res.locked.u128()), (0, 800));
+    let err = transfer(&mut deps, env_at(None, Some(24)), ALICE, LENDING, 101).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::TokensLocked {
+            locked: Uint128::new(800)
+        }
+    );
+    transfer(&mut deps, env_at(None, Some(24)), ALICE, LENDING, 100).unwrap();
+
+    // Linear from the start once the cliff has passed.
+    let res = vesting_at(&deps, 25);
+    assert_eq!((res.vested.u128(), res.locked.u128()), (200, 600));
+    transfer(&mut deps, env_at(None, Some(50)), ALICE, LENDING, 400).unwrap();
+    assert!(transfer(&mut deps, env_at(None, Some(50)), ALICE, LENDING, 1).is_err());
+
+    transfer(&mut deps, env_at(None, Some(100)), ALICE, LENDING, 400).unwrap();
+    assert!(balance(&deps, ALICE).is_zero());
+}
+
+#[test]
+fn locked_tokens_cannot_be_burned_or_spent_by_others() {
+    let mut deps = setup(true);
+    let burn = ExecuteMsg::Burn {
+        amount: Uint128::new(101),
+    };
+    let err = execute(deps.as_mut(), mock_env(), mock_info(ALICE, &[]), burn).unwrap_err();
+    assert!(matches!(err, ContractError::TokensLocked { .. }));
+
+    let msg = ExecuteMsg::IncreaseAllowance {
+        spender: LENDING.to_string(),
+        amount: Uint128::new(900),
+        expires: None,
+    };
+    execute(deps.as_mut(), mock_env(), mock_info(ALICE, &[]), msg).unwrap();
+    let msg = ExecuteMsg::TransferFrom {
+        owner: ALICE.to_string(),
+        recipient: LENDING.to_string(),
+        amount: Uint128::new(101),
+    };
+    let err = execute(deps.as_mut(), mock_env(), mock_info(LENDING, &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::TokensLocked { .. }));
+}
+
+#[test]
+fn invalid_schedule() {
+    let mut deps = setup(false);
+    let msg = ExecuteMsg::MintVested {
+        recipient: ALICE.to_string(),
+        amount: Uint128::new(100),
+        schedule: VestingSchedule {
+            start_time: 10,
+            cliff_time: 5,
+            end_time: 20,
+        },
+    };
+    let err = execute(deps.as_mut(), mock_env(), mock_info(MINTER, &[]), msg).unwrap_err();
+    assert_eq!(err, ContractError::InvalidSchedule {});
+}
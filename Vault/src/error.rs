@@ -0,0 +1,39 @@
+use cosmwasm_std::{OverflowError, StdError};
+use cw_utils::ParseReplyError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ContractError {
+    #[error("{0}")]
+    Std(#[from] StdError),
+
+    #[error("{0}")]
+    ParseReply(#[from] ParseReplyError),
+
+    #[error("{0}")]
+    Overflow(#[from] OverflowError),
+
+    #[error("Unauthorized")]
+    Unauthorized {},
+
+    #[error("Invalid CW20 hook")]
+    InvalidCw20Hook {},
+
+    #[error("Amount must be greater than zero")]
+    InvalidZeroAmount {},
+
+    #[error("Deposit is too small to mint any shares")]
+    ZeroShares {},
+
+    #[error("Pool rewards are not paid in the vault's USD token")]
+    UnsupportedRewardToken {},
+
+    #[error("The pool's unbonding period keeps withdrawals from settling")]
+    UnbondingNotSupported {},
+
+    #[error("Share token has not been instantiated")]
+    ShareTokenNotSet {},
+
+    #[error("Unknown reply id: {id}")]
+    UnknownReplyId { id: u64 },
+}
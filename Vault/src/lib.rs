@@ -0,0 +1,377 @@
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
+use cosmwasm_std::{
+    from_json, to_json_binary, Addr, Binary, Deps, DepsMut, Env, MessageInfo, OverflowError,
+    OverflowOperation, Reply, Response, StdResult, SubMsg, Uint128, WasmMsg,
+};
+use cw20::{BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg, Cw20ReceiveMsg, MinterResponse};
+use cw_utils::parse_reply_instantiate_data;
+use lending_dapp::msg::{
+    ConfigResponse as LendingConfigResponse, ExecuteMsg as LendingExecuteMsg,
+    PendingRewardsResponse, QueryMsg as LendingQueryMsg, Cw20HookMsg as LendingHookMsg,
+    UserInfoResponse,
+};
+
+pub mod error;
+pub mod msg;
+pub mod state;
+#[cfg(test)]
+mod tests;
+
+use crate::error::ContractError;
+use crate::msg::{
+    ConfigResponse, ExecuteMsg, InstantiateMsg, QueryMsg, ReceiveMsg, VaultInfoResponse,
+};
+use crate::state::{Config, CONFIG, TOTAL_SHARES};
+
+const INSTANTIATE_SHARE_REPLY_ID: u64 = 1;
+const SHARE_NAME: &str = "Lending Vault USD Share";
+const SHARE_SYMBOL: &str = "vUSD";
+const SHARE_DECIMALS: u8 = 6;
+/// Shares the first deposit pays for but nobody receives. They keep a
+/// donation to a near-empty vault from pricing later deposits down to zero
+/// shares, since most of the donation would accrue to them.
+pub const MINIMUM_SHARES: Uint128 = Uint128::new(1_000);
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn instantiate(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: InstantiateMsg,
+) -> Result<Response, ContractError> {
+    let config = Config {
+        owner: info.sender.clone(),
+        lending: deps.api.addr_validate(&msg.lending)?,
+        usd_token: deps.api.addr_validate(&msg.usd_token)?,
+        share_token: None,
+    };
+    check_reward_token(deps.as_ref(), &env, &config)?;
+    check_no_unbonding(deps.as_ref(), &config)?;
+    CONFIG.save(deps.storage, &config)?;
+    TOTAL_SHARES.save(deps.storage, &Uint128::zero())?;
+
+    let share_msg = token::msg::InstantiateMsg {
+        base: cw20_base::msg::InstantiateMsg {
+            name: SHARE_NAME.to_string(),
+            symbol: SHARE_SYMBOL.to_string(),
+            decimals: SHARE_DECIMALS,
+            initial_balances: vec![],
+            mint: Some(MinterResponse {
+                minter: env.contract.address.to_string(),
+                cap: None,
+            }),
+            marketing: None,
+        },
+        controller: None,
+    };
+    let instantiate_share = WasmMsg::Instantiate {
+        admin: Some(info.sender.to_string()),
+        code_id: msg.share_code_id,
+        msg: to_json_binary(&share_msg)?,
+        funds: vec![],
+        label: SHARE_NAME.to_string(),
+    };
+
+    Ok(Response::new()
+        .add_submessage(SubMsg::reply_on_success(instantiate_share, INSTANTIATE_SHARE_REPLY_ID))
+        .add_attribute("method", "instantiate"))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
+    match msg.id {
+        INSTANTIATE_SHARE_REPLY_ID => {
+            let res = parse_reply_instantiate_data(msg)?;
+            let share_token = deps.api.addr_validate(&res.contract_address)?;
+            CONFIG.update(deps.storage, |mut config| -> StdResult<_> {
+                config.share_token = Some(share_token.clone());
+                Ok(config)
+            })?;
+            Ok(Response::new()
+                .add_attribute("method", "reply_instantiate_share")
+                .add_attribute("share_token", share_token))
+        }
+        id => Err(ContractError::UnknownReplyId { id }),
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    match msg {
+        ExecuteMsg::Receive(msg) => receive_cw20(deps, env, info, msg),
+        ExecuteMsg::Compound {} => execute::compound(deps, env),
+        ExecuteMsg::Reinvest {} => execute::reinvest(deps, env, info),
+    }
+}
+
+pub fn receive_cw20(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    cw20_msg: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let sender = deps.api.addr_validate(&cw20_msg.sender)?;
+    match from_json(&cw20_msg.msg) {
+        Ok(ReceiveMsg::Deposit {}) if info.sender == config.usd_token => {
+            execute::deposit(deps, env, sender, cw20_msg.amount)
+        }
+        Ok(ReceiveMsg::Withdraw {}) if config.share_token.as_ref() == Some(&info.sender) => {
+            execute::withdraw(deps, env, sender, cw20_msg.amount)
+        }
+        _ => Err(ContractError::InvalidCw20Hook {}),
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> Result<Binary, ContractError> {
+    let res = match msg {
+        QueryMsg::GetConfig {} => to_json_binary(&query::config(deps)?),
+        QueryMsg::GetVaultInfo {} => to_json_binary(&query::vault_info(deps, env)?),
+    };
+    Ok(res?)
+}
+
+mod execute {
+    use super::*;
+
+    pub fn deposit(deps: DepsMut, env: Env, sender: Addr, amount: Uint128) -> Result<Response, ContractError> {
+        if amount.is_zero() {
+            return Err(ContractError::InvalidZeroAmount {});
+        }
+
+        let config = CONFIG.load(deps.storage)?;
+        check_no_unbonding(deps.as_ref(), &config)?;
+        let total_shares = TOTAL_SHARES.load(deps.storage)?;
+
+        // The deposit is already part of the vault's idle balance.
+        let total_assets = total_assets(deps.as_ref(), &env, &config)?.checked_sub(amount)?;
+        // The first deposit also pays for the locked `MINIMUM_SHARES`.
+        let (shares, locked) = if total_shares.is_zero() {
+            (amount.saturating_sub(MINIMUM_SHARES), MINIMUM_SHARES)
+        } else if total_assets.is_zero() {
+            (amount, Uint128::zero())
+        } else {
+            (mul_ratio(amount, total_shares, total_assets)?, Uint128::zero())
+        };
+        if shares.is_zero() {
+            return Err(ContractError::ZeroShares {});
+        }
+        let total_shares = total_shares.checked_add(shares)?.checked_add(locked)?;
+        TOTAL_SHARES.save(deps.storage, &total_shares)?;
+
+        let mint = WasmMsg::Execute {
+            contract_addr: share_token(&config)?.to_string(),
+            msg: to_json_binary(&Cw20ExecuteMsg::Mint {
+                recipient: sender.to_string(),
+                amount: shares,
+            })?,
+            funds: vec![],
+        };
+
+        Ok(Response::new()
+            .add_message(supply_msg(&config, amount)?)
+            .add_message(mint)
+            .add_attribute("action", "deposit")
+            .add_attribute("amount", amount.to_string())
+            .add_attribute("shares", shares.to_string()))
+    }
+
+    pub fn withdraw(deps: DepsMut, env: Env, sender: Addr, shares: Uint128) -> Result<Response, ContractError> {
+        if shares.is_zero() {
+            return Err(ContractError::InvalidZeroAmount {});
+        }
+
+        let config = CONFIG.load(deps.storage)?;
+        let total_shares = TOTAL_SHARES.load(deps.storage)?;
+        let total_assets = total_assets(deps.as_ref(), &env, &config)?;
+
+        let assets = mul_ratio(shares, total_assets, total_shares)?;
+        // Pay out of idle funds first, then pull the rest from the pool.
+        let idle = idle_balance(deps.as_ref(), &env, &config)?;
+        let from_pool = assets.saturating_sub(idle);
+        if !from_pool.is_zero() {
+            check_no_unbonding(deps.as_ref(), &config)?;
+        }
+        TOTAL_SHARES.save(deps.storage, &total_shares.checked_sub(shares)?)?;
+
+        let burn = WasmMsg::Execute {
+            contract_addr: share_token(&config)?.to_string(),
+            msg: to_json_binary(&Cw20ExecuteMsg::Burn { amount: shares })?,
+            funds: vec![],
+        };
+        let mut res = Response::new().add_message(burn);
+
+        if !from_pool.is_zero() {
+            res = res.add_message(WasmMsg::Execute {
+                contract_addr: config.lending.to_string(),
+                msg: to_json_binary(&LendingExecuteMsg::Unstake { amount: from_pool })?,
+                funds: vec![],
+            });
+        }
+
+        let payout = WasmMsg::Execute {
+            contract_addr: config.usd_token.to_string(),
+            msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: sender.to_string(),
+                amount: assets,
+            })?,
+            funds: vec![],
+        };
+
+        Ok(res
+            .add_message(payout)
+            .add_attribute("action", "withdraw")
+            .add_attribute("shares", shares.to_string())
+            .add_attribute("amount", assets.to_string()))
+    }
+
+    pub fn compound(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+        let config = CONFIG.load(deps.storage)?;
+        let pending = check_reward_token(deps.as_ref(), &env, &config)?;
+
+        let mut res = Response::new().add_attribute("action", "compound");
+        if !pending.pending.is_zero() {
+            res = res.add_message(WasmMsg::Execute {
+                contract_addr: config.lending.to_string(),
+                msg: to_json_binary(&LendingExecuteMsg::ClaimRewards {})?,
+                funds: vec![],
+            });
+        }
+
+        let reinvest = WasmMsg::Execute {
+            contract_addr: env.contract.address.to_string(),
+            msg: to_json_binary(&ExecuteMsg::Reinvest {})?,
+            funds: vec![],
+        };
+
+        Ok(res
+            .add_message(reinvest)
+            .add_attribute("claimed", pending.pending.to_string()))
+    }
+
+    pub fn reinvest(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+        if info.sender != env.contract.address {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        let config = CONFIG.load(deps.storage)?;
+        let idle = idle_balance(deps.as_ref(), &env, &config)?;
+
+        let mut res = Response::new()
+            .add_attribute("action", "reinvest")
+            .add_attribute("amount", idle.to_string());
+        if !idle.is_zero() {
+            res = res.add_message(supply_msg(&config, idle)?);
+        }
+        Ok(res)
+    }
+
+    fn supply_msg(config: &Config, amount: Uint128) -> StdResult<WasmMsg> {
+        Ok(WasmMsg::Execute {
+            contract_addr: config.usd_token.to_string(),
+            msg: to_json_binary(&Cw20ExecuteMsg::Send {
+                contract: config.lending.to_string(),
+                amount,
+                msg: to_json_binary(&LendingHookMsg::Stake { on_behalf_of: None })?,
+            })?,
+            funds: vec![],
+        })
+    }
+
+    fn share_token(config: &Config) -> Result<Addr, ContractError> {
+        config.share_token.clone().ok_or(ContractError::ShareTokenNotSet {})
+    }
+}
+
+/// The vault's pending pool rewards. Fails if any are owed in a token other
+/// than USD, which `Reinvest` could not supply back to the pool.
+fn check_reward_token(
+    deps: Deps,
+    env: &Env,
+    config: &Config,
+) -> Result<PendingRewardsResponse, ContractError> {
+    let pending: PendingRewardsResponse = deps.querier.query_wasm_smart(
+        &config.lending,
+        &LendingQueryMsg::GetPendingRewards {
+            address: env.contract.address.to_string(),
+        },
+    )?;
+    let usd = config.usd_token.as_str();
+    let current = pending.reward_token.as_deref().is_none_or(|token| token == usd);
+    let settled = pending.settled.iter().all(|coin| coin.address == usd);
+    if !current || !settled {
+        return Err(ContractError::UnsupportedRewardToken {});
+    }
+    Ok(pending)
+}
+
+/// Fails if the pool has an unbonding period, since `Unstake` would then
+/// only queue the funds a withdrawal pays out in the same transaction.
+fn check_no_unbonding(deps: Deps, config: &Config) -> Result<(), ContractError> {
+    let pool: LendingConfigResponse = deps
+        .querier
+        .query_wasm_smart(&config.lending, &LendingQueryMsg::GetConfig {})?;
+    if pool.unbonding_period > 0 {
+        return Err(ContractError::UnbondingNotSupported {});
+    }
+    Ok(())
+}
+
+/// USD the vault holds that has not been supplied to the pool.
+fn idle_balance(deps: Deps, env: &Env, config: &Config) -> StdResult<Uint128> {
+    let balance: BalanceResponse = deps.querier.query_wasm_smart(
+        &config.usd_token,
+        &Cw20QueryMsg::Balance {
+            address: env.contract.address.to_string(),
+        },
+    )?;
+    Ok(balance.balance)
+}
+
+fn total_assets(deps: Deps, env: &Env, config: &Config) -> Result<Uint128, ContractError> {
+    let supplied: UserInfoResponse = deps.querier.query_wasm_smart(
+        &config.lending,
+        &LendingQueryMsg::GetUserInfo {
+            address: env.contract.address.to_string(),
+        },
+    )?;
+
+    Ok(supplied.staked_amount.checked_add(idle_balance(deps, env, config)?)?)
+}
+
+/// `value * numerator / denominator`, rounded down. Share prices round
+/// against the depositor on the way in and the withdrawer on the way out.
+fn mul_ratio(value: Uint128, numerator: Uint128, denominator: Uint128) -> Result<Uint128, ContractError> {
+    value
+        .checked_multiply_ratio(numerator, denominator)
+        .map_err(|_| OverflowError::new(OverflowOperation::Mul, value, numerator).into())
+}
+
+mod query {
+    use super::*;
+
+    pub fn config(deps: Deps) -> StdResult<ConfigResponse> {
+        let config = CONFIG.load(deps.storage)?;
+        Ok(ConfigResponse {
+            owner: config.owner.to_string(),
+            lending: config.lending.to_string(),
+            usd_token: config.usd_token.to_string(),
+            share_token: config.share_token.map(|addr| addr.to_string()),
+        })
+    }
+
+    pub fn vault_info(deps: Deps, env: Env) -> Result<VaultInfoResponse, ContractError> {
+        let config = CONFIG.load(deps.storage)?;
+        Ok(VaultInfoResponse {
+            total_shares: TOTAL_SHARES.load(deps.storage)?,
+            total_assets: total_assets(deps, &env, &config)?,
+        })
+    }
+}
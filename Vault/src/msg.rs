@@ -0,0 +1,63 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use cosmwasm_std::Uint128;
+use cw20::Cw20ReceiveMsg;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InstantiateMsg {
+    /// The `lending_dapp` pool that deposits are supplied to.
+    pub lending: String,
+    pub usd_token: String,
+    /// Code id of the `token` contract used for vault shares.
+    pub share_code_id: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    Receive(Cw20ReceiveMsg),
+    /// Claims pending pool rewards and re-supplies every idle USD the vault
+    /// holds. Callable by anyone. Fails while pool rewards are paid, or owed
+    /// to the vault, in any other token.
+    Compound {},
+    /// Second half of `Compound`, run once the claimed rewards have arrived.
+    /// Only callable by the vault itself.
+    Reinvest {},
+}
+
+/// Messages accepted through a CW20 `Send`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiveMsg {
+    /// Sent with USD: supplies it to the pool and mints shares to the sender.
+    /// The first deposit must exceed the `MINIMUM_SHARES` locked in the
+    /// vault.
+    Deposit {},
+    /// Sent with shares: burns them and pays out their USD value. Deposits,
+    /// and withdrawals that need funds from the pool, fail while the pool
+    /// has an unbonding period.
+    Withdraw {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    GetConfig {},
+    GetVaultInfo {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ConfigResponse {
+    pub owner: String,
+    pub lending: String,
+    pub usd_token: String,
+    /// Unset until the share token instantiated by the vault replies.
+    pub share_token: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VaultInfoResponse {
+    pub total_shares: Uint128,
+    /// USD supplied to the pool plus USD held idle by the vault.
+    pub total_assets: Uint128,
+}
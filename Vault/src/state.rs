@@ -0,0 +1,17 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use cosmwasm_std::{Addr, Uint128};
+use cw_storage_plus::Item;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Config {
+    pub owner: Addr,
+    pub lending: Addr,
+    pub usd_token: Addr,
+    /// Set once the share token instantiated by this contract replies.
+    pub share_token: Option<Addr>,
+}
+
+pub const CONFIG: Item<Config> = Item::new("config");
+/// Includes the `MINIMUM_SHARES` locked by the first deposit.
+pub const TOTAL_SHARES: Item<Uint128> = Item::new("total_shares");
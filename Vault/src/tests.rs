@@ -0,0 +1,436 @@
+use cosmwasm_std::{to_json_binary, Addr, Decimal, Empty, Uint128};
+use cw20::{BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg, MinterResponse};
+use cw_multi_test::{App, AppResponse, Contract, ContractWrapper, Executor};
+
+use crate::error::ContractError;
+use crate::msg::{
+    ConfigResponse, ExecuteMsg, InstantiateMsg, QueryMsg, ReceiveMsg, VaultInfoResponse,
+};
+
+const OWNER: &str = "owner";
+const ALICE: &str = "alice";
+const BOB: &str = "bob";
+
+fn token_contract() -> Box<dyn Contract<Empty>> {
+    Box::new(ContractWrapper::new(
+        token::contract::execute,
+        token::contract::instantiate,
+        token::contract::query,
+    ))
+}
+
+fn lending_contract() -> Box<dyn Contract<Empty>> {
+    Box::new(
+        ContractWrapper::new(
+            lending_dapp::execute,
+            lending_dapp::instantiate,
+            lending_dapp::query,
+        )
+        .with_reply(lending_dapp::reply),
+    )
+}
+
+fn vault_contract() -> Box<dyn Contract<Empty>> {
+    Box::new(
+        ContractWrapper::new(crate::execute, crate::instantiate, crate::query)
+            .with_reply(crate::reply),
+    )
+}
+
+struct Suite {
+    app: App,
+    usd: Addr,
+    om: Addr,
+    lending: Addr,
+    vault: Addr,
+    vault_id: u64,
+    shares: Addr,
+}
+
+fn setup() -> Suite {
+    let mut app = App::default();
+    let token_id = app.store_code(token_contract());
+    let lending_id = app.store_code(lending_contract());
+    let vault_id = app.store_code(vault_contract());
+
+    let new_token = |app: &mut App, symbol: &str| {
+        let msg = token::msg::InstantiateMsg {
+            base: cw20_base::msg::InstantiateMsg {
+                name: symbol.to_string(),
+                symbol: symbol.to_string(),
+                decimals: 6,
+                initial_balances: vec![],
+                mint: Some(MinterResponse {
+                    minter: OWNER.to_string(),
+                    cap: None,
+                }),
+                marketing: None,
+            },
+            controller: None,
+        };
+        app.instantiate_contract(token_id, Addr::unchecked(OWNER), &msg, &[], symbol, None)
+            .unwrap()
+    };
+    let usd = new_token(&mut app, "USD");
+    let om = new_token(&mut app, "OMT");
+
+    let lending = app
+        .instantiate_contract(
+            lending_id,
+            Addr::unchecked(OWNER),
+            &lending_dapp::msg::InstantiateMsg {
+                usd_token: usd.to_string(),
+                om_token: om.to_string(),
+                collateral_ratio: Uint128::new(80),
+                interest_rate: Uint128::new(10),
+                penalty_rate: Uint128::new(5),
+                rate_slope: Uint128::new(20),
+                rebalance_utilization: Uint128::new(90),
+                unbonding_period: 0,
+                guardian: OWNER.to_string(),
+                timelock_delay: 3_600,
+                auction_start_price: Decimal::one(),
+                auction_step: Decimal::permille(1),
+                liquidation_bonus: Uint128::new(5),
+                close_factor: Uint128::new(50),
+                receipt_code_id: token_id,
+            },
+            &[],
+            "lending",
+            None,
+        )
+        .unwrap();
+
+    let vault = app
+        .instantiate_contract(
+            vault_id,
+            Addr::unchecked(OWNER),
+            &InstantiateMsg {
+                lending: lending.to_string(),
+                usd_token: usd.to_string(),
+                share_code_id: token_id,
+            },
+            &[],
+            "vault",
+            None,
+        )
+        .unwrap();
+
+    let config: ConfigResponse = app
+        .wrap()
+        .query_wasm_smart(&vault, &QueryMsg::GetConfig {})
+        .unwrap();
+
+    Suite {
+        app,
+        usd,
+        om,
+        lending,
+        vault,
+        vault_id,
+        shares: Addr::unchecked(config.share_token.unwrap()),
+    }
+}
+
+impl Suite {
+    fn mint_usd(&mut self, recipient: &str, amount: u128) {
+        self.app
+            .execute_contract(
+                Addr::unchecked(OWNER),
+                self.usd.clone(),
+                &Cw20ExecuteMsg::Mint {
+                    recipient: recipient.to_string(),
+                    amount: Uint128::new(amount),
+                },
+                &[],
+            )
+            .unwrap();
+    }
+
+    fn send(
+        &mut self,
+        sender: &str,
+        token: &Addr,
+        amount: u128,
+        msg: &ReceiveMsg,
+    ) -> Result<AppResponse, ContractError> {
+        self.app
+            .execute_contract(
+                Addr::unchecked(sender),
+                token.clone(),
+                &Cw20ExecuteMsg::Send {
+                    contract: self.vault.to_string(),
+                    amount: Uint128::new(amount),
+                    msg: to_json_binary(msg).unwrap(),
+                },
+                &[],
+            )
+            .map_err(|err| err.downcast().unwrap())
+    }
+
+    /// Mints USD to `user` and deposits it.
+    fn deposit(&mut self, user: &str, amount: u128) -> Result<AppResponse, ContractError> {
+        self.mint_usd(user, amount);
+        let usd = self.usd.clone();
+        self.send(user, &usd, amount, &ReceiveMsg::Deposit {})
+    }
+
+    fn withdraw(&mut self, user: &str, shares: u128) {
+        let token = self.shares.clone();
+        self.send(user, &token, shares, &ReceiveMsg::Withdraw {}).unwrap();
+    }
+
+    fn compound(&mut self) -> Result<AppResponse, ContractError> {
+        self.app
+            .execute_contract(Addr::unchecked(ALICE), self.vault.clone(), &ExecuteMsg::Compound {}, &[])
+            .map_err(|err| err.downcast().unwrap())
+    }
+
+    fn update_rewards(&mut self, token: &Addr, supply_rate: u128) {
+        self.app
+            .execute_contract(
+                Addr::unchecked(OWNER),
+                self.lending.clone(),
+                &lending_dapp::msg::ExecuteMsg::UpdateRewards {
+                    reward_token: token.to_string(),
+                    supply_rate: Uint128::new(supply_rate),
+                    borrow_rate: Uint128::zero(),
+                },
+                &[],
+            )
+            .unwrap();
+    }
+
+    /// Sets the pool's unbonding period through its timelock.
+    fn set_unbonding_period(&mut self, period: u64) {
+        let change = lending_dapp::msg::ConfigChange {
+            unbonding_period: Some(period),
+            ..Default::default()
+        };
+        let owner = Addr::unchecked(OWNER);
+        self.app
+            .execute_contract(
+                owner.clone(),
+                self.lending.clone(),
+                &lending_dapp::msg::ExecuteMsg::QueueConfigChange {
+                    change: Box::new(change),
+                },
+                &[],
+            )
+            .unwrap();
+        self.app.update_block(|block| block.time = block.time.plus_seconds(3_600));
+        self.app
+            .execute_contract(
+                owner,
+                self.lending.clone(),
+                &lending_dapp::msg::ExecuteMsg::ExecuteConfigChange { id: 1 },
+                &[],
+            )
+            .unwrap();
+    }
+
+    fn balance(&self, token: &Addr, address: &str) -> Uint128 {
+        let res: BalanceResponse = self
+            .app
+            .wrap()
+            .query_wasm_smart(
+                token,
+                &Cw20QueryMsg::Balance {
+                    address: address.to_string(),
+                },
+            )
+            .unwrap();
+        res.balance
+    }
+
+    fn vault_info(&self) -> VaultInfoResponse {
+        self.app
+            .wrap()
+            .query_wasm_smart(&self.vault, &QueryMsg::GetVaultInfo {})
+            .unwrap()
+    }
+}
+
+#[test]
+fn deposit_mints_shares_and_supplies_pool() {
+    let mut suite = setup();
+    suite.deposit(ALICE, 1_000_000).unwrap();
+
+    // The first `MINIMUM_SHARES` are locked in the vault.
+    assert_eq!(suite.balance(&suite.shares, ALICE), Uint128::new(999_000));
+    assert_eq!(suite.balance(&suite.usd, suite.lending.as_str()), Uint128::new(1_000_000));
+    assert_eq!(
+        suite.vault_info(),
+        VaultInfoResponse {
+            total_shares: Uint128::new(1_000_000),
+            total_assets: Uint128::new(1_000_000),
+        }
+    );
+}
+
+#[test]
+fn compound_reinvests_rewards() {
+    let mut suite = setup();
+    suite.deposit(ALICE, 1_000_000).unwrap();
+
+    // Pay supply rewards in USD so they can be re-supplied directly.
+    let usd = suite.usd.clone();
+    suite.update_rewards(&usd, 1);
+    suite.mint_usd(OWNER, 100);
+    suite
+        .app
+        .execute_contract(
+            Addr::unchecked(OWNER),
+            suite.usd.clone(),
+            &Cw20ExecuteMsg::Send {
+                contract: suite.lending.to_string(),
+                amount: Uint128::new(100),
+                msg: to_json_binary(&lending_dapp::msg::Cw20HookMsg::FundRewards {}).unwrap(),
+            },
+            &[],
+        )
+        .unwrap();
+    suite.app.update_block(|block| block.time = block.time.plus_seconds(100));
+
+    suite.compound().unwrap();
+    assert_eq!(suite.vault_info().total_assets, Uint128::new(1_000_100));
+    assert_eq!(suite.balance(&suite.usd, suite.vault.as_str()), Uint128::zero());
+
+    suite.withdraw(ALICE, 999_000);
+    assert_eq!(suite.balance(&suite.usd, ALICE), Uint128::new(999_099));
+    assert_eq!(suite.vault_info().total_shares, Uint128::new(1_000));
+}
+
+#[test]
+fn donation_does_not_inflate_share_price() {
+    let mut suite = setup();
+    let err = suite.deposit(ALICE, 1_000).unwrap_err();
+    assert!(matches!(err, ContractError::ZeroShares {}));
+
+    // ALICE takes the only share and donates to the vault to inflate its
+    // price, but the locked shares take most of the donation.
+    suite.deposit(ALICE, 1_001).unwrap();
+    assert_eq!(suite.balance(&suite.shares, ALICE), Uint128::new(1));
+    suite.mint_usd(ALICE, 1_000_000);
+    suite
+        .app
+        .execute_contract(
+            Addr::unchecked(ALICE),
+            suite.usd.clone(),
+            &Cw20ExecuteMsg::Transfer {
+                recipient: suite.vault.to_string(),
+                amount: Uint128::new(1_000_000),
+            },
+            &[],
+        )
+        .unwrap();
+
+    let err = suite.deposit(BOB, 500).unwrap_err();
+    assert!(matches!(err, ContractError::ZeroShares {}));
+    suite.deposit(BOB, 500_000).unwrap();
+
+    // The USD from the rejected first deposit is still with ALICE.
+    suite.withdraw(ALICE, 1);
+    assert_eq!(suite.balance(&suite.usd, ALICE), Uint128::new(1_000 + 1_000));
+    let shares = suite.balance(&suite.shares, BOB).u128();
+    suite.withdraw(BOB, shares);
+    assert!(suite.balance(&suite.usd, BOB) >= Uint128::new(499_000));
+}
+
+#[test]
+fn non_usd_rewards_are_rejected() {
+    let mut suite = setup();
+    suite.deposit(ALICE, 1_000_000).unwrap();
+    let om = suite.om.clone();
+    suite.update_rewards(&om, 1);
+    let err = suite.compound().unwrap_err();
+    assert!(matches!(err, ContractError::UnsupportedRewardToken {}));
+
+    let err = suite
+        .app
+        .instantiate_contract(
+            suite.vault_id,
+            Addr::unchecked(OWNER),
+            &InstantiateMsg {
+                lending: suite.lending.to_string(),
+                usd_token: suite.usd.to_string(),
+                share_code_id: 1,
+            },
+            &[],
+            "vault",
+            None,
+        )
+        .unwrap_err();
+    assert!(matches!(err.downcast().unwrap(), ContractError::UnsupportedRewardToken {}));
+}
+
+#[test]
+fn pool_unbonding_is_rejected() {
+    let mut suite = setup();
+    suite.deposit(ALICE, 1_000_000).unwrap();
+    suite.set_unbonding_period(86_400);
+
+    let err = suite.deposit(BOB, 1_000).unwrap_err();
+    assert!(matches!(err, ContractError::UnbondingNotSupported {}));
+    let shares = suite.shares.clone();
+    let err = suite
+        .send(ALICE, &shares, 1_000, &ReceiveMsg::Withdraw {})
+        .unwrap_err();
+    assert!(matches!(err, ContractError::UnbondingNotSupported {}));
+    assert_eq!(suite.balance(&suite.shares, ALICE), Uint128::new(999_000));
+
+    let err = suite
+        .app
+        .instantiate_contract(
+            suite.vault_id,
+            Addr::unchecked(OWNER),
+            &InstantiateMsg {
+                lending: suite.lending.to_string(),
+                usd_token: suite.usd.to_string(),
+                share_code_id: 1,
+            },
+            &[],
+            "vault",
+            None,
+        )
+        .unwrap_err();
+    assert!(matches!(err.downcast().unwrap(), ContractError::UnbondingNotSupported {}));
+}
+
+#[test]
+fn share_math_overflow_is_an_error() {
+    let err = crate::mul_ratio(Uint128::MAX, Uint128::new(2), Uint128::one()).unwrap_err();
+    assert!(matches!(err, ContractError::Overflow(_)));
+    assert_eq!(
+        crate::mul_ratio(Uint128::MAX, Uint128::MAX, Uint128::MAX).unwrap(),
+        Uint128::MAX
+    );
+}
+
+#[test]
+fn config_query_returns_addresses() {
+    let suite = setup();
+    let config: ConfigResponse = suite
+        .app
+        .wrap()
+        .query_wasm_smart(&suite.vault, &QueryMsg::GetConfig {})
+        .unwrap();
+    assert_eq!(
+        config,
+        ConfigResponse {
+            owner: OWNER.to_string(),
+            lending: suite.lending.to_string(),
+            usd_token: suite.usd.to_string(),
+            share_token: Some(suite.shares.to_string()),
+        }
+    );
+}
+
+#[test]
+fn reinvest_is_internal() {
+    let mut suite = setup();
+    suite
+        .app
+        .execute_contract(Addr::unchecked(ALICE), suite.vault.clone(), &ExecuteMsg::Reinvest {}, &[])
+        .unwrap_err();
+}